@@ -0,0 +1,319 @@
+use crate::domain::Event;
+use chrono::{DateTime, Utc};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Connections are handled one after the other, so a client that never sends its request must
+/// not be able to stall the server
+const REQUEST_READ_TIMEOUT: Duration = Duration::from_secs(5);
+/// We only need the request line and there is no reason to buffer more than this of it
+const MAX_REQUEST_LINE_BYTES: u64 = 8192;
+
+/// Starts a minimal HTTP server in its own thread that serves today's events as JSON on
+/// `GET /agenda` and all known events as an ical feed on `GET /calendar.ics`. It only listens on
+/// localhost since the agenda is nobody else's business.
+pub fn start_agenda_server(
+    port: u16,
+    events: Arc<Mutex<Vec<Event>>>,
+    calendar_events: Arc<Mutex<Vec<Event>>>,
+) {
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Could not start agenda server on port {}: {}", port, e);
+            return;
+        }
+    };
+    println!("Serving the agenda on http://127.0.0.1:{}/agenda", port);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = handle_connection(stream, &events, &calendar_events) {
+                        eprintln!("Error handling agenda request: {}", e);
+                    }
+                }
+                Err(e) => eprintln!("Error accepting agenda connection: {}", e),
+            }
+        }
+    });
+}
+
+fn handle_connection(
+    mut stream: TcpStream,
+    events: &Mutex<Vec<Event>>,
+    calendar_events: &Mutex<Vec<Event>>,
+) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_READ_TIMEOUT))?;
+    let mut request_line = String::new();
+    BufReader::new((&stream).take(MAX_REQUEST_LINE_BYTES)).read_line(&mut request_line)?;
+    let mut request_parts = request_line.split_whitespace();
+    let (status, content_type, body) = match (request_parts.next(), request_parts.next()) {
+        (Some("GET"), Some("/agenda")) => (
+            "200 OK",
+            "application/json",
+            events_to_json(&events.lock().unwrap(), Utc::now()),
+        ),
+        (Some("GET"), Some("/calendar.ics")) => (
+            "200 OK",
+            "text/calendar; charset=utf-8",
+            events_to_ical(&calendar_events.lock().unwrap()),
+        ),
+        _ => (
+            "404 Not Found",
+            "application/json",
+            "{\"error\":\"not found\"}".to_string(),
+        ),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn json_optional_string(value: &Option<String>) -> String {
+    match value {
+        Some(value) => json_string(value),
+        None => "null".to_string(),
+    }
+}
+
+/// Saves scripts from doing time calculations, negative when the event already started. All day
+/// events have no start time to count down to.
+fn minutes_until_start(event: &Event, now: DateTime<Utc>) -> String {
+    if event.all_day {
+        "null".to_string()
+    } else {
+        (event.start_timestamp.with_timezone(&Utc) - now)
+            .num_minutes()
+            .to_string()
+    }
+}
+
+fn event_to_json(event: &Event, now: DateTime<Utc>) -> String {
+    format!(
+        "{{\"uid\":{},\"summary\":{},\"description\":{},\"location\":{},\"meeturl\":{},\"dialin\":{},\"all_day\":{},\"busy\":{},\"start\":{},\"end\":{},\"minutes_until_start\":{}}}",
+        json_string(&event.uid),
+        json_string(&event.summary),
+        json_string(&event.description),
+        json_string(&event.location),
+        json_optional_string(&event.meeturl),
+        json_optional_string(&event.dialin),
+        event.all_day,
+        event.busy,
+        json_string(&event.start_timestamp.to_rfc3339()),
+        json_string(&event.end_timestamp.to_rfc3339()),
+        minutes_until_start(event, now)
+    )
+}
+
+fn events_to_json(events: &[Event], now: DateTime<Utc>) -> String {
+    format!(
+        "[{}]",
+        events
+            .iter()
+            .map(|event| event_to_json(event, now))
+            .collect::<Vec<String>>()
+            .join(",")
+    )
+}
+
+/// Escapes a value of type TEXT, see <https://tools.ietf.org/html/rfc5545#section-3.3.11>
+fn ical_text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Lines longer than 75 octets must be folded onto continuation lines that start with a space,
+/// we never split a UTF-8 sequence. See <https://tools.ietf.org/html/rfc5545#section-3.1>
+fn fold_ical_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 2);
+    let mut line_length = 0;
+    for c in line.chars() {
+        if line_length + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            line_length = 1;
+        }
+        folded.push(c);
+        line_length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+/// Every occurrence becomes an event of its own, so its UID is made unique with its start time
+fn event_to_ical(event: &Event, dtstamp: &str) -> String {
+    let utc_format = "%Y%m%dT%H%M%SZ";
+    let mut lines = vec![
+        "BEGIN:VEVENT".to_string(),
+        format!(
+            "UID:{}",
+            ical_text(&format!(
+                "{}-{}",
+                event.uid,
+                event.start_timestamp.with_timezone(&Utc).format(utc_format)
+            ))
+        ),
+        format!("DTSTAMP:{}", dtstamp),
+    ];
+    if event.all_day {
+        let start_date = event.start_timestamp.date().naive_local();
+        // the end date is exclusive, an all-day event without a proper end lasts for one day
+        let end_date = event
+            .end_timestamp
+            .date()
+            .naive_local()
+            .max(start_date.succ());
+        lines.push(format!(
+            "DTSTART;VALUE=DATE:{}",
+            start_date.format("%Y%m%d")
+        ));
+        lines.push(format!("DTEND;VALUE=DATE:{}", end_date.format("%Y%m%d")));
+    } else {
+        lines.push(format!(
+            "DTSTART:{}",
+            event.start_timestamp.with_timezone(&Utc).format(utc_format)
+        ));
+        lines.push(format!(
+            "DTEND:{}",
+            event.end_timestamp.with_timezone(&Utc).format(utc_format)
+        ));
+    }
+    lines.push(format!("SUMMARY:{}", ical_text(&event.summary)));
+    if !event.location.is_empty() {
+        lines.push(format!("LOCATION:{}", ical_text(&event.location)));
+    }
+    if !event.description.is_empty() {
+        lines.push(format!("DESCRIPTION:{}", ical_text(&event.description)));
+    }
+    if let Some(meeturl) = &event.meeturl {
+        lines.push(format!("URL:{}", meeturl));
+    }
+    if !event.busy {
+        lines.push("TRANSP:TRANSPARENT".to_string());
+    }
+    lines.push("END:VEVENT".to_string());
+    lines.iter().map(|line| fold_ical_line(line)).collect()
+}
+
+fn events_to_ical(events: &[Event]) -> String {
+    let dtstamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    format!(
+        "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//aggregat4//meeters//EN\r\n{}END:VCALENDAR\r\n",
+        events
+            .iter()
+            // to-dos are only shown as events in the menu, they are not events of the calendar
+            .filter(|event| !event.todo)
+            .map(|event| event_to_ical(event, &dtstamp))
+            .collect::<String>()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use chrono_tz::Europe::Berlin;
+
+    #[test]
+    fn json_strings_are_escaped() {
+        assert_eq!(
+            "\"Say \\\"hi\\\"\\n\\\\o/\\u0007\"",
+            json_string("Say \"hi\"\n\\o/\u{7}")
+        );
+    }
+
+    #[test]
+    fn no_events_is_an_empty_array() {
+        assert_eq!("[]", events_to_json(&[], Utc::now()));
+    }
+
+    #[test]
+    fn ical_text_is_escaped() {
+        assert_eq!(
+            "Lunch\\, then \\;review\\n\\\\o/",
+            ical_text("Lunch, then ;review\n\\o/")
+        );
+    }
+
+    fn all_day_event(summary: &str, todo: bool) -> Event {
+        let start = Berlin.ymd(2022, 3, 10).and_hms(0, 0, 0);
+        Event {
+            uid: summary.to_string(),
+            summary: summary.to_string(),
+            description: "".to_string(),
+            location: "".to_string(),
+            meeturl: None,
+            meeting_provider: None,
+            dialin: None,
+            passcode: None,
+            comments: vec![],
+            attachments: vec![],
+            nof_inline_attachments: 0,
+            busy: false,
+            tentative: false,
+            priority: 0,
+            sequence: 0,
+            last_modified: None,
+            num_participants: 0,
+            is_organizer: false,
+            related_to: vec![],
+            alarm_lead_seconds: vec![],
+            calendar_name: None,
+            todo,
+            all_day: true,
+            start_timestamp: start,
+            end_timestamp: start,
+        }
+    }
+
+    #[test]
+    fn exported_all_day_events_last_at_least_a_day_and_todos_are_left_out() {
+        let ical = events_to_ical(&[
+            all_day_event("Offsite", false),
+            all_day_event("Submit report", true),
+        ]);
+        assert!(ical.contains("DTSTART;VALUE=DATE:20220310\r\nDTEND;VALUE=DATE:20220311\r\n"));
+        assert_eq!(1, ical.matches("BEGIN:VEVENT").count());
+        assert!(!ical.contains("Submit report"));
+    }
+
+    #[test]
+    fn long_ical_lines_are_folded_without_splitting_characters() {
+        let line = format!("SUMMARY:{}", "ä".repeat(40));
+        let folded = fold_ical_line(&line);
+        let folded_lines: Vec<&str> = folded.trim_end_matches("\r\n").split("\r\n").collect();
+        assert_eq!(2, folded_lines.len());
+        assert!(folded_lines.iter().all(|line| line.len() <= 75));
+        assert_eq!(line, folded_lines.join("").replacen(' ', "", 1));
+    }
+}
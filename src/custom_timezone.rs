@@ -13,6 +13,33 @@ pub struct CustomTz {
     pub timespanset: FixedTimespanSet,
 }
 
+impl CustomTz {
+    /// Lists the transition points of this timezone with the offset that applies from each of
+    /// them on, followed by the offset for the given UTC time. Meant as a diagnostic for times
+    /// that are off by an hour.
+    pub fn describe(&self, utc_now: &NaiveDateTime) -> String {
+        let mut lines = vec![format!(
+            "Custom timezone '{}' with {} transition(s), before the first one the offset is {}",
+            self.name,
+            self.timespanset.rest.len(),
+            self.timespanset.first.fix()
+        )];
+        for (start, timespan) in &self.timespanset.rest {
+            lines.push(format!(
+                "  from {} UTC: {}",
+                NaiveDateTime::from_timestamp(*start, 0),
+                timespan.fix()
+            ));
+        }
+        lines.push(format!(
+            "  at {} UTC the offset is {}",
+            utc_now.format("%Y-%m-%d %H:%M:%S"),
+            self.offset_from_utc_datetime(utc_now).fix()
+        ));
+        lines.join("\n")
+    }
+}
+
 /// This struct models a custom timezone consisting of spans of time order oldest to newest.
 /// Each span is a slice of time where particular offsets versus UTC need to be applied.
 /// The total offset is the sum of the utc_offset and the dst_offset.
@@ -445,4 +472,30 @@ mod tests {
         assert_eq!(59, localtime_first_span.minute());
         assert_eq!(0, localtime_first_span.second());
     }
+
+    #[test]
+    fn describe_lists_transitions_and_current_offset() {
+        let mytz = CustomTz {
+            name: "India".to_string(),
+            timespanset: FixedTimespanSet {
+                first: FixedTimespan {
+                    utc_offset: 0,
+                    dst_offset: 0,
+                    name: "",
+                },
+                rest: vec![(
+                    1627812000,
+                    FixedTimespan {
+                        utc_offset: 19800,
+                        dst_offset: 0,
+                        name: "",
+                    },
+                )],
+            },
+        };
+        assert_eq!(
+            "Custom timezone 'India' with 1 transition(s), before the first one the offset is +00:00\n  from 2021-08-01 10:00:00 UTC: +05:30\n  at 2021-08-02 00:00:00 UTC the offset is +05:30",
+            mytz.describe(&NaiveDate::from_ymd(2021, 8, 2).and_hms(0, 0, 0))
+        );
+    }
 }
@@ -4,27 +4,77 @@ use std::fmt;
 
 // From https://doc.rust-lang.org/stable/rust-by-example/error/multiple_error_types/define_error_type.html and message added
 #[derive(Debug, Clone)]
-pub struct CalendarError {
-    // Type "String" means that the struct owns and stores the string, if I would use a string reference (&str)
-    // I would also need to specify a lifecycle like  "msg: &'a str,". This is less storage
-    // but it means we can't just generate error messages on the fly that are not static
-    // If I _would_ use a refernce we need to suffix the CalendarError type with a lifetime like
-    // CalendarError<'a>
-    pub msg: String,
+pub enum CalendarError {
+    /// The calendar could not be downloaded, e.g. because the network is down
+    Network { msg: String },
+    /// The server answered with an error status
+    Http { status: u16, msg: String },
+    /// The calendar or one of its events is malformed
+    Parse { msg: String },
+    /// A timezone can not be resolved or its definition is malformed
+    Timezone { msg: String },
+    /// The event is valid but uses something we do not support
+    UnsupportedEvent { msg: String },
+}
+
+impl CalendarError {
+    pub fn msg(&self) -> &str {
+        match self {
+            CalendarError::Network { msg }
+            | CalendarError::Http { msg, .. }
+            | CalendarError::Parse { msg }
+            | CalendarError::Timezone { msg }
+            | CalendarError::UnsupportedEvent { msg } => msg,
+        }
+    }
 }
 
 impl fmt::Display for CalendarError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Calendar Error: {}", self.msg)
+        write!(f, "Calendar Error: {}", self.msg())
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Event {
+    pub uid: String,
     pub summary: String,
     pub description: String,
     pub location: String,
     pub meeturl: Option<String>,
+    /// The name of the service the online meeting is hosted on, e.g. "Zoom" or "Teams"
+    pub meeting_provider: Option<String>,
+    /// A phone number to dial into the meeting, optionally followed by " PIN <pin>"
+    pub dialin: Option<String>,
+    /// The passcode needed to join the meeting when the client asks for it
+    pub passcode: Option<String>,
+    /// Notes from the COMMENT properties of the event
+    pub comments: Vec<String>,
+    /// URLs of documents attached to the event
+    pub attachments: Vec<String>,
+    /// Binary attachments are embedded in the calendar, we only keep track of how many there are
+    pub nof_inline_attachments: usize,
+    /// Whether the event blocks time, events marked as free time do not count as meetings
+    pub busy: bool,
+    /// Whether the event is only tentatively planned or accepted
+    pub tentative: bool,
+    /// The priority from 1 (highest) to 9 (lowest), 0 if the priority is undefined
+    pub priority: u8,
+    /// The revision of the event, incremented by the organizer for every significant change
+    pub sequence: u32,
+    pub last_modified: Option<DateTime<Tz>>,
+    /// The number of attendees of the event, including ourselves
+    pub num_participants: usize,
+    /// Whether the configured user is the organizer of the event
+    pub is_organizer: bool,
+    /// The UIDs of events this event is related to, e.g. the kickoff of a series of meetings
+    pub related_to: Vec<String>,
+    /// How many seconds before the start the alarms (VALARM) of the event go off, longest first
+    pub alarm_lead_seconds: Vec<i64>,
+    /// The name of the calendar the event comes from, if the calendar has one
+    pub calendar_name: Option<String>,
+    /// Whether this is a to-do that is shown on the day it is due instead of an actual event
+    pub todo: bool,
     pub all_day: bool,
     pub start_timestamp: DateTime<Tz>,
     pub end_timestamp: DateTime<Tz>,
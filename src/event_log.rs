@@ -0,0 +1,91 @@
+use crate::domain::Event;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// A persistent record of things that happened to events, like the user dismissing an event or
+/// us notifying about it. Since it is stored on disk it survives a restart of the application,
+/// so we do not notify twice about the same event.
+///
+/// Entries are keyed by the UID of the event and its start time (see `dismissal_key` and
+/// `notification_key`). This way an entry for one occurrence of a recurring meeting does not
+/// affect the entire series. For each entry we also remember when the event ends so that we can
+/// throw away entries that are no longer relevant.
+pub struct EventLog {
+    path: PathBuf,
+    // maps the key of the entry to the end of the event in seconds since the epoch
+    entries: HashMap<String, i64>,
+}
+
+pub fn dismissal_key(event: &Event) -> String {
+    format!("{}@{}", event.uid, event.start_timestamp.timestamp())
+}
+
+/// Notifications are keyed by how long before the start of the event they were shown so that
+/// notifications at different times before the same event are tracked separately
+pub fn notification_key(event: &Event, lead_seconds: i64) -> String {
+    format!(
+        "{}@{}-{}",
+        event.uid,
+        event.start_timestamp.timestamp(),
+        lead_seconds
+    )
+}
+
+impl EventLog {
+    /// Loads the entries from the given file. A missing or unreadable file just means that
+    /// nothing was recorded yet.
+    pub fn load(path: PathBuf) -> EventLog {
+        let mut entries = HashMap::new();
+        if let Ok(contents) = fs::read_to_string(&path) {
+            for line in contents.lines() {
+                // each line is of the form "<key>\t<end timestamp>"
+                match line.rsplit_once('\t') {
+                    Some((key, end)) => match end.parse::<i64>() {
+                        Ok(end_timestamp) => {
+                            entries.insert(key.to_string(), end_timestamp);
+                        }
+                        Err(e) => eprintln!("Ignoring invalid event log entry '{}': {}", line, e),
+                    },
+                    None => eprintln!("Ignoring invalid event log entry '{}'", line),
+                }
+            }
+        }
+        EventLog { path, entries }
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub fn add(&mut self, key: String, event: &Event) {
+        self.entries.insert(key, event.end_timestamp.timestamp());
+        self.save();
+    }
+
+    /// Removes all entries for events that ended before the given time, they can never trigger a
+    /// notification again.
+    pub fn prune(&mut self, now_timestamp: i64) {
+        let nof_entries = self.entries.len();
+        self.entries
+            .retain(|_key, end_timestamp| *end_timestamp >= now_timestamp);
+        if self.entries.len() != nof_entries {
+            self.save();
+        }
+    }
+
+    fn save(&self) {
+        let contents = self
+            .entries
+            .iter()
+            .map(|(key, end_timestamp)| format!("{}\t{}\n", key, end_timestamp))
+            .collect::<String>();
+        if let Err(e) = fs::write(&self.path, contents) {
+            eprintln!(
+                "Could not save event log to {}: {}",
+                self.path.to_str().unwrap(),
+                e
+            );
+        }
+    }
+}
@@ -12,6 +12,43 @@ pub fn find_property_value(properties: &[Property], name: &str) -> Option<String
     None
 }
 
+/// Finds the value of the property with the given name whose LANGUAGE parameter matches the
+/// preferred language. When there is no such property we fall back to the property without a
+/// LANGUAGE parameter and finally to the first property with that name.
+pub fn find_localized_property_value(
+    properties: &[Property],
+    name: &str,
+    preferred_language: Option<&str>,
+) -> Option<String> {
+    let candidates: Vec<&Property> = properties.iter().filter(|p| p.name == name).collect();
+    let language_of = |property: &Property| {
+        property
+            .params
+            .as_ref()
+            .and_then(|params| find_param(params, "LANGUAGE"))
+            .and_then(|values| values.first().cloned())
+    };
+    let preferred = preferred_language.and_then(|preferred_language| {
+        candidates.iter().find(|property| {
+            language_of(property).map_or(false, |language| {
+                // a preferred language of "de" should also match "de-CH"
+                language.eq_ignore_ascii_case(preferred_language)
+                    || language.split('-').next().map_or(false, |primary| {
+                        primary.eq_ignore_ascii_case(preferred_language)
+                    })
+            })
+        })
+    });
+    preferred
+        .or_else(|| {
+            candidates
+                .iter()
+                .find(|property| language_of(property).is_none())
+        })
+        .or_else(|| candidates.first())
+        .and_then(|property| property.value.clone())
+}
+
 pub fn find_property<'a>(properties: &'a [Property], name: &str) -> Option<&'a Property> {
     for property in properties {
         if property.name == name {
@@ -31,11 +68,15 @@ pub fn find_param<'a>(params: &'a [(String, Vec<String>)], name: &str) -> Option
     None
 }
 
+/// Joins the values of a parameter with commas. Values that contain a delimiter of the content
+/// line or a space must be quoted so they parse as one value again.
+///
+/// See <https://tools.ietf.org/html/rfc5545#section-3.1>
 pub fn format_param_values(param_values: &[String]) -> String {
     param_values
         .iter()
         .map(|param_val| {
-            if param_val.contains(' ') {
+            if param_val.contains(&[' ', ',', ';', ':'][..]) {
                 format!("\"{}\"", param_val)
             } else {
                 param_val.to_string()
@@ -55,7 +96,7 @@ pub fn params_to_string(params: &[(String, Vec<String>)]) -> String {
                 .iter()
                 .map(|param| format!("{}={}", param.0, format_param_values(&param.1)))
                 .collect::<Vec<String>>()
-                .join(",")
+                .join(";")
         );
     }
 }
@@ -83,7 +124,6 @@ pub fn is_ical_date(prop: &Property) -> bool {
         && &find_param(prop.params.as_ref().unwrap(), "VALUE").unwrap()[0] == "DATE"
 }
 
-#[allow(dead_code)]
 pub fn ical_event_to_string(event: &IcalEvent) -> String {
     properties_to_string(&event.properties)
 }
@@ -141,4 +181,73 @@ mod tests {
 
         assert_eq!("FOO:bar\nbaz:qux", ical_event_to_string(&event));
     }
+
+    fn parse_property(line: &str) -> Property {
+        ical::PropertyParser::from_reader(line.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn property_with_quoted_params_round_trips() {
+        for line in &[
+            "DTSTART;TZID=\"W. Europe Standard Time\";VALUE=DATE-TIME:20220301T090000",
+            "ATTENDEE;DELEGATED-FROM=\"mailto:a@example.com\",\"mailto:b@example.com\":mailto:c@example.com",
+            "X-FOO;X-LIST=\"a,b\";X-SEP=\"c;d\":value",
+        ] {
+            let prop = parse_property(line);
+            assert_eq!(*line, prop_to_string(&prop));
+            let reparsed = parse_property(&prop_to_string(&prop));
+            assert_eq!(prop.params, reparsed.params);
+            assert_eq!(prop.value, reparsed.value);
+        }
+    }
+
+    fn summary_prop(language: Option<&str>, value: &str) -> Property {
+        let mut prop = Property::new();
+        prop.name = "SUMMARY".to_string();
+        prop.params =
+            language.map(|language| vec![("LANGUAGE".to_string(), vec![language.to_string()])]);
+        prop.value = Some(value.to_string());
+        prop
+    }
+
+    #[test]
+    fn localized_property_prefers_matching_language() {
+        let props = vec![
+            summary_prop(Some("en"), "Meeting"),
+            summary_prop(None, "Default"),
+            summary_prop(Some("de-DE"), "Besprechung"),
+        ];
+        assert_eq!(
+            Some("Besprechung".to_string()),
+            find_localized_property_value(&props, "SUMMARY", Some("de"))
+        );
+        assert_eq!(
+            Some("Meeting".to_string()),
+            find_localized_property_value(&props, "SUMMARY", Some("EN"))
+        );
+    }
+
+    #[test]
+    fn localized_property_falls_back_to_untagged_then_first() {
+        let props = vec![
+            summary_prop(Some("en"), "Meeting"),
+            summary_prop(None, "Default"),
+        ];
+        assert_eq!(
+            Some("Default".to_string()),
+            find_localized_property_value(&props, "SUMMARY", Some("fr"))
+        );
+        assert_eq!(
+            Some("Default".to_string()),
+            find_localized_property_value(&props, "SUMMARY", None)
+        );
+        let tagged_only = vec![summary_prop(Some("en"), "Meeting")];
+        assert_eq!(
+            Some("Meeting".to_string()),
+            find_localized_property_value(&tagged_only, "SUMMARY", Some("fr"))
+        );
+    }
 }
@@ -1,43 +1,317 @@
+use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use std::thread;
 
 use chrono::prelude::*;
 use chrono_tz::Tz;
 use directories::ProjectDirs;
+use glib::ToVariant;
+use gtk::gio;
 use gtk::prelude::*;
 use gtk::Menu;
 use libappindicator::{AppIndicator, AppIndicatorStatus};
-use notify_rust::Notification;
+use notify_rust::Urgency;
+use regex::Regex;
+use std::convert::TryFrom;
 
 use crate::domain::Event;
-use crate::CalendarMessages::{EventNotification, TodayEvents};
+use crate::CalendarMessages::{
+    AutoJoin, EventEndNotification, EventNotification, EventUpdatedNotification, HolidayEvents,
+    MorningSummary, PinnedEvents, TodayEvents,
+};
 use domain::CalendarError;
+use event_log::{dismissal_key, notification_key, EventLog};
+use notifications::{
+    AutoBackend, DbusBackend, NotificationBackend, NotificationContent, NotifySendBackend,
+};
+use pinned_meetings::PinnedMeetings;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use text_util::truncate_with_ellipsis;
 
+mod agenda_server;
 mod binary_search;
 mod custom_timezone;
 mod domain;
+mod event_log;
 mod ical_util;
 mod meeters_ical;
+mod meeting_url;
+mod notifications;
+mod pinned_meetings;
+mod text_util;
 mod timezones;
 mod windows_timezones;
 
-fn get_ical(url: &str) -> Result<String, CalendarError> {
+/// Calendars that are paginated are merged from at most this many pages
+const MAX_CALENDAR_PAGES: usize = 20;
+
+/// Downloads the calendar. Some aggregation services paginate their feeds and point to the next
+/// page with a `Link: <...>; rel="next"` header, we follow those links and merge the pages into
+/// one calendar.
+fn get_ical(
+    agent: &ureq::Agent,
+    url: &str,
+    credentials: &Option<(String, String)>,
+    user_agent: &str,
+) -> Result<String, CalendarError> {
+    let mut pages = vec![];
+    let mut next_url = Some(url.to_string());
+    while let Some(page_url) = next_url {
+        if pages.len() == MAX_CALENDAR_PAGES {
+            eprintln!(
+                "Calendar has more than {} pages, ignoring the rest",
+                MAX_CALENDAR_PAGES
+            );
+            break;
+        }
+        let (page, link_header) = get_ical_page(agent, &page_url, credentials, user_agent)?;
+        pages.push(page);
+        next_url = link_header.and_then(|link_header| next_page_url(&page_url, &link_header));
+    }
+    Ok(merge_calendar_pages(&pages))
+}
+
+/// Downloads a single page of the calendar, returns its text and the Link header, if any
+fn get_ical_page(
+    agent: &ureq::Agent,
+    url: &str,
+    credentials: &Option<(String, String)>,
+    user_agent: &str,
+) -> Result<(String, Option<String>), CalendarError> {
     println!("trying to fetch ical");
-    match ureq::get(url).timeout(Duration::new(10, 0)).call() {
-        Ok(response) => match response.into_string() {
-            Ok(body) => Ok(body),
-            Err(e) => Err(CalendarError {
-                msg: format!("Error getting calendar response body as text: {}", e),
-            }),
-        },
-        Err(e) => Err(CalendarError {
+    // some calendar servers reject requests without a proper User-Agent
+    let mut request = agent
+        .get(url)
+        .timeout(Duration::new(10, 0))
+        .set("User-Agent", user_agent);
+    if let Some((username, password)) = credentials {
+        request = request.set(
+            "Authorization",
+            &format!(
+                "Basic {}",
+                base64::encode(format!("{}:{}", username, password))
+            ),
+        );
+    }
+    match request.call() {
+        Ok(response) => {
+            let transfer_encoding = response
+                .header("Content-Transfer-Encoding")
+                .map(|encoding| encoding.trim().to_lowercase());
+            let link_header = response.header("Link").map(str::to_string);
+            match response.into_string() {
+                Ok(body) => decode_transfer_encoding(transfer_encoding.as_deref(), body)
+                    .map(|text| (text, link_header)),
+                Err(e) => Err(CalendarError::Network {
+                    msg: format!("Error getting calendar response body as text: {}", e),
+                }),
+            }
+        }
+        Err(ureq::Error::Status(status, _)) => Err(CalendarError::Http {
+            status,
+            msg: format!("Error getting ical from url: status code {}", status),
+        }),
+        Err(e) => Err(CalendarError::Network {
             msg: format!("Error getting ical from url: {}", e),
         }),
     }
 }
 
+/// Finds the target of the link with relation "next" in a Link header and resolves it against
+/// the URL of the current page.
+///
+/// See <https://tools.ietf.org/html/rfc8288#section-3>
+fn next_page_url(page_url: &str, link_header: &str) -> Option<String> {
+    link_header.split(',').find_map(|link| {
+        let (target, params) = link.trim().strip_prefix('<')?.split_once('>')?;
+        let is_next = params.split(';').any(|param| {
+            match param.trim().split_once('=') {
+                // the relation can be a space separated list of relation types
+                Some((name, value)) if name.trim().eq_ignore_ascii_case("rel") => value
+                    .trim()
+                    .trim_matches('"')
+                    .split_whitespace()
+                    .any(|relation| relation.eq_ignore_ascii_case("next")),
+                _ => false,
+            }
+        });
+        if is_next {
+            url::Url::parse(page_url)
+                .and_then(|base| base.join(target))
+                .map(|next_url| next_url.to_string())
+                .ok()
+        } else {
+            None
+        }
+    })
+}
+
+/// Each page of a paginated calendar is a complete VCALENDAR. We keep the first one and move the
+/// components (events, timezones, ...) of all further pages into it.
+fn merge_calendar_pages(pages: &[String]) -> String {
+    let mut merged = match pages.first() {
+        Some(first_page) => first_page.clone(),
+        None => return "".to_string(),
+    };
+    let mut components = String::new();
+    for page in &pages[1..] {
+        let mut depth = 0;
+        for line in page.lines() {
+            let is_begin = line.starts_with("BEGIN:");
+            if is_begin {
+                depth += 1;
+            }
+            // everything below the VCALENDAR itself belongs to a component
+            if depth >= 2 {
+                components.push_str(line.trim_end_matches('\r'));
+                components.push('\n');
+            }
+            if line.starts_with("END:") {
+                depth -= 1;
+            }
+        }
+    }
+    match merged.rfind("END:VCALENDAR") {
+        Some(end_index) => merged.insert_str(end_index, &components),
+        None => merged.push_str(&components),
+    }
+    merged
+}
+
+/// Builds a TLS configuration that authenticates us with a client certificate, for calendar
+/// servers that require mutual TLS. Server certificates are verified against the usual web roots.
+fn load_client_tls_config(
+    cert_path: &str,
+    key_path: &str,
+) -> Result<Arc<rustls::ClientConfig>, String> {
+    let open = |path: &str| {
+        fs::File::open(path)
+            .map(std::io::BufReader::new)
+            .map_err(|e| format!("Can not open {}: {}", path, e))
+    };
+    let certs: Vec<rustls::Certificate> = rustls_pemfile::certs(&mut open(cert_path)?)
+        .map_err(|e| format!("Can not read certificates from {}: {}", cert_path, e))?
+        .into_iter()
+        .map(rustls::Certificate)
+        .collect();
+    if certs.is_empty() {
+        return Err(format!("There is no PEM certificate in {}", cert_path));
+    }
+    let key = rustls_pemfile::read_all(&mut open(key_path)?)
+        .map_err(|e| format!("Can not read the private key from {}: {}", key_path, e))?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(rustls::PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| format!("There is no PEM private key in {}", key_path))?;
+    let mut root_store = rustls::RootCertStore::empty();
+    root_store.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(
+        |trust_anchor| {
+            rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+                trust_anchor.subject,
+                trust_anchor.spki,
+                trust_anchor.name_constraints,
+            )
+        },
+    ));
+    let config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(root_store)
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("The client certificate and key can not be used: {}", e))?;
+    Ok(Arc::new(config))
+}
+
+/// Downloads the calendar and extracts its events. A calendar that can not be parsed after a
+/// successful download is usually a truncated response, so in that case we try exactly once more
+/// right away instead of waiting for the next polling interval.
+fn download_events(
+    agent: &ureq::Agent,
+    url: &str,
+    credentials: &Option<(String, String)>,
+    user_agent: &str,
+    local_tz: &Tz,
+    options: &meeters_ical::ExtractOptions,
+    cache: &mut meeters_ical::ExtractionCache,
+) -> Result<Vec<Event>, CalendarError> {
+    let text = get_ical(agent, url, credentials, user_agent)?;
+    match cache.extract_events(&text, local_tz, options) {
+        // timezone problems or unsupported events will not go away by downloading again
+        Err(e @ CalendarError::Parse { .. }) => {
+            eprintln!(
+                "Error parsing the calendar, downloading it once more: {}",
+                e
+            );
+            get_ical(agent, url, credentials, user_agent)
+                .and_then(|text| cache.extract_events(&text, local_tz, options))
+        }
+        result => result,
+    }
+}
+
+/// Some misbehaving servers and proxies send the calendar with a Content-Transfer-Encoding, which
+/// is a MIME header that has no business in HTTP. We decode the body in that case.
+fn decode_transfer_encoding(
+    transfer_encoding: Option<&str>,
+    body: String,
+) -> Result<String, CalendarError> {
+    let decoded_bytes = match transfer_encoding {
+        Some("quoted-printable") => decode_quoted_printable(&body),
+        Some("base64") => {
+            let base64_text: String = body.chars().filter(|c| !c.is_whitespace()).collect();
+            base64::decode(base64_text).map_err(|e| CalendarError::Parse {
+                msg: format!("Error decoding the base64 encoded calendar: {}", e),
+            })?
+        }
+        _ => return Ok(body),
+    };
+    String::from_utf8(decoded_bytes).map_err(|e| CalendarError::Parse {
+        msg: format!("The decoded calendar is not valid UTF-8: {}", e),
+    })
+}
+
+/// Decodes quoted-printable text, see <https://tools.ietf.org/html/rfc2045#section-6.7>. Invalid
+/// escape sequences are kept as they are.
+fn decode_quoted_printable(text: &str) -> Vec<u8> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'=' {
+            decoded.push(bytes[i]);
+            i += 1;
+        } else if bytes[i + 1..].starts_with(b"\r\n") {
+            // soft line break
+            i += 3;
+        } else if bytes[i + 1..].starts_with(b"\n") {
+            i += 2;
+        } else {
+            match bytes
+                .get(i + 1..i + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 3;
+                }
+                None => {
+                    decoded.push(b'=');
+                    i += 1;
+                }
+            }
+        }
+    }
+    decoded
+}
+
 fn has_icons(dir: &Path) -> bool {
     let normal_icon_path = dir.with_file_name("meeters-appindicator.png");
     let error_icon_path = dir.with_file_name("meeters-appindicator-error.png");
@@ -68,6 +342,18 @@ fn set_error_icon(indicator: &mut AppIndicator) {
     }
 }
 
+/// Shown when we could not get the calendar but are still showing the events we got before
+fn set_stale_icon(indicator: &mut AppIndicator) {
+    if let Some(icon_path) = find_icon_path() {
+        let stale_icon_path = icon_path.with_file_name("meeters-appindicator-stale.png");
+        if stale_icon_path.exists() {
+            indicator.set_icon(stale_icon_path.to_str().unwrap());
+        } else {
+            set_error_icon(indicator);
+        }
+    }
+}
+
 fn set_some_meetings_left_icon(indicator: &mut libappindicator::AppIndicator) {
     if let Some(icon_path) = find_icon_path() {
         indicator.set_icon(
@@ -104,7 +390,7 @@ fn get_icon_path_with_fallbak(icon_path: PathBuf, icon_filename: String) -> Path
 }
 
 fn create_indicator() -> AppIndicator {
-    let mut indicator = AppIndicator::new("meeters", "");
+    let mut indicator = AppIndicator::new(&instance_id(), "");
     indicator.set_status(AppIndicatorStatus::Active);
     match find_icon_path() {
         Some(icon_path) => {
@@ -126,6 +412,69 @@ fn create_indicator() -> AppIndicator {
     }
 }
 
+/// Whether the app indicator is shown in the tray
+#[derive(Clone, Copy)]
+enum TrayIconMode {
+    /// Show the indicator unless we can tell that there is no tray to show it in
+    Auto,
+    Always,
+    /// Only notify about meetings
+    Never,
+}
+
+/// Whether a StatusNotifier host is running that can show the app indicator. Without one, for
+/// example on Wayland desktops without a tray extension, the indicator is silently invisible.
+fn has_status_notifier_host() -> bool {
+    let connection = match gio::bus_get_sync(gio::BusType::Session, None::<&gio::Cancellable>) {
+        Ok(connection) => connection,
+        Err(_) => return false,
+    };
+    connection
+        .call_sync(
+            Some("org.freedesktop.DBus"),
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "NameHasOwner",
+            Some(&("org.kde.StatusNotifierWatcher",).to_variant()),
+            Some(glib::VariantTy::new("(b)").unwrap()),
+            gio::DBusCallFlags::NONE,
+            -1,
+            None::<&gio::Cancellable>,
+        )
+        .ok()
+        .and_then(|reply| reply.get::<(bool,)>())
+        .map_or(false, |(has_owner,)| has_owner)
+}
+
+/// Decides whether to show the tray icon. When there is no StatusNotifier host on X11,
+/// libappindicator can still fall back to the legacy system tray, on Wayland there is no such
+/// fallback and we only notify about meetings.
+fn should_show_tray_icon(tray_icon_mode: TrayIconMode) -> bool {
+    match tray_icon_mode {
+        TrayIconMode::Always => true,
+        TrayIconMode::Never => {
+            println!("The tray icon is disabled, meeters only shows notifications");
+            false
+        }
+        TrayIconMode::Auto => {
+            if has_status_notifier_host() {
+                true
+            } else if std::env::var("WAYLAND_DISPLAY").is_ok() {
+                eprintln!(
+                    "No StatusNotifier host (org.kde.StatusNotifierWatcher) is running on the session bus, so the tray icon can not be shown. \
+                     meeters only shows notifications. Install a tray extension for your desktop or set MEETERS_TRAY_ICON=always to show the icon anyway."
+                );
+                false
+            } else {
+                eprintln!(
+                    "No StatusNotifier host (org.kde.StatusNotifierWatcher) is running on the session bus, the tray icon is only visible if your desktop has a legacy system tray"
+                );
+                true
+            }
+        }
+    }
+}
+
 fn open_meeting(meet_url: &str) {
     match gtk::show_uri(None, meet_url, gtk::current_event_time()) {
         Ok(_) => (),
@@ -133,68 +482,551 @@ fn open_meeting(meet_url: &str) {
     }
 }
 
-fn create_indicator_menu(events: &[domain::Event], indicator: &mut AppIndicator) {
-    let mut m: Menu = gtk::Menu::new();
-    let mut nof_upcoming_meetings = 0;
-    if events.is_empty() {
-        let item = gtk::MenuItem::with_label("test");
-        let label = item.child().unwrap();
-        (label.downcast::<gtk::Label>())
-            .unwrap()
-            .set_markup("<b>No Events Today</b>");
-        m.append(&item);
+/// Splits a command template like "firefox --new-window {url}" into the program and its
+/// arguments. Without a {url} placeholder the URL is passed as the last argument.
+fn command_from_template(template: &str, meet_url: &str) -> Vec<String> {
+    let mut command: Vec<String> = template
+        .split_whitespace()
+        .map(|part| part.replace("{url}", meet_url))
+        .collect();
+    if !template.contains("{url}") {
+        command.push(meet_url.to_string());
+    }
+    command
+}
+
+/// Opens the meeting with the command configured for its provider in MEETERS_OPEN_<PROVIDER>,
+/// e.g. MEETERS_OPEN_ZOOM, and with the default handler for the URL otherwise
+fn open_provider_meeting(meet_url: &str, provider: Option<&str>) {
+    let template = provider.and_then(|provider| {
+        dotenvy::var(format!("MEETERS_OPEN_{}", provider.to_uppercase())).ok()
+    });
+    if let Some(template) = template {
+        let command = command_from_template(&template, meet_url);
+        if let Some((program, args)) = command.split_first() {
+            match std::process::Command::new(program).args(args).spawn() {
+                Ok(_) => return,
+                Err(e) => eprintln!(
+                    "Error running '{}' to open the meeting, using the default handler: {}",
+                    program, e
+                ),
+            }
+        }
+    }
+    open_meeting(meet_url);
+}
+
+/// Converts a dial-in of the form "<number> PIN <pin>" into a tel: URI that dials the number and
+/// then enters the PIN after a short pause.
+fn dialin_to_tel_uri(dialin: &str) -> String {
+    let (number, pin) = match dialin.split_once(" PIN ") {
+        Some((number, pin)) => (number, Some(pin)),
+        None => (dialin, None),
+    };
+    let number: String = number
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '+')
+        .collect();
+    match pin {
+        Some(pin) => format!("tel:{},,{}#", number, pin),
+        None => format!("tel:{}", number),
+    }
+}
+
+/// Determines the order of the events in the indicator menu
+#[derive(Clone, Copy)]
+enum MenuSort {
+    /// All events ordered by their start time
+    Chronological,
+    /// Meetings that are still running or upcoming come before the meetings that are over,
+    /// all day events stay at the top
+    UpcomingFirst,
+}
+
+/// The day whose meetings the indicator menu shows
+#[derive(Clone, Copy, PartialEq)]
+enum MenuDay {
+    Today,
+    /// After MEETERS_DAY_ROLLOVER_HOUR the menu shows the meetings of the next day
+    Tomorrow,
+}
+
+impl MenuDay {
+    fn name(&self) -> &'static str {
+        match self {
+            MenuDay::Today => "Today",
+            MenuDay::Tomorrow => "Tomorrow",
+        }
+    }
+}
+
+/// All configuration that influences how the indicator menu is rendered
+#[derive(Clone, Copy)]
+struct MenuConfig {
+    sort: MenuSort,
+    /// Whether meetings that are over are collapsed into a submenu
+    compact: bool,
+    /// Whether to show the duration of a meeting instead of its end time
+    show_duration: bool,
+    /// Whether to show the number of participants of a meeting
+    show_participants: bool,
+    /// The maximum number of characters of a meeting title before it is cut off
+    max_label_len: Option<usize>,
+    /// A second timezone in which the start of meetings is shown, e.g. that of a remote team
+    secondary_tz: Option<Tz>,
+    /// Whether meetings that are over are left out of the menu
+    hide_past: bool,
+    /// Whether to show the name of the calendar a meeting comes from
+    show_calendar_name: bool,
+}
+
+/// Holidays are only shown as context for the day: they never count as meetings and we never
+/// notify about them, so they are kept apart from the other events and marked as free time.
+fn holidays_in_range(events: &[Event], start: DateTime<Tz>, end: DateTime<Tz>) -> Vec<Event> {
+    meeters_ical::events_in_range(events, start, end)
+        .into_iter()
+        .map(|mut event| {
+            event.busy = false;
+            event
+        })
+        .collect()
+}
+
+/// Formats a duration in a compact way like "45m", "1h" or "1h30m"
+fn format_duration(duration: chrono::Duration) -> String {
+    let hours = duration.num_hours();
+    let minutes = duration.num_minutes() % 60;
+    if hours == 0 {
+        format!("{}m", minutes)
+    } else if minutes == 0 {
+        format!("{}h", hours)
+    } else {
+        format!("{}h{}m", hours, minutes)
+    }
+}
+
+/// Adds the start time of the event in the secondary timezone, like "09:00 - 10:00 / 03:00 EDT".
+/// The conversion happens per event so the offset is always correct for the day of the event.
+fn with_secondary_time(time_string: String, event: &Event, secondary_tz: Option<Tz>) -> String {
+    match secondary_tz {
+        Some(secondary_tz) if !event.all_day => format!(
+            "{} / {}",
+            time_string,
+            event
+                .start_timestamp
+                .with_timezone(&secondary_tz)
+                .format("%H:%M %Z")
+        ),
+        _ => time_string,
+    }
+}
+
+fn format_event_time(event: &Event, show_duration: bool) -> String {
+    if event.all_day {
+        "All Day".to_owned()
+    } else if event.start_timestamp == event.end_timestamp {
+        // reminders without a duration are shown at their time, they are not all day events
+        event.start_timestamp.format("%H:%M").to_string()
+    } else if show_duration {
+        format!(
+            "{} ({})",
+            &event.start_timestamp.format("%H:%M"),
+            format_duration(event.end_timestamp - event.start_timestamp)
+        )
+    } else {
+        format!(
+            "{} - {}",
+            &event.start_timestamp.format("%H:%M"),
+            &event.end_timestamp.format("%H:%M")
+        )
+    }
+}
+
+/// Events span the half-open interval [start, end) as in RFC 5545, so of two back-to-back
+/// meetings only the second one is running at the shared boundary
+fn is_past_event(event: &Event, now: &DateTime<Local>) -> bool {
+    !event.all_day && *now >= event.end_timestamp
+}
+
+fn is_running_event(event: &Event, now: &DateTime<Local>) -> bool {
+    *now >= event.start_timestamp && *now < event.end_timestamp
+}
+
+/// The events to show in the menu in the configured order, without the past events if those
+/// should be hidden
+fn get_menu_events<'a>(
+    events: &'a [Event],
+    menu_config: MenuConfig,
+    now: &DateTime<Local>,
+) -> Vec<&'a Event> {
+    sort_menu_events(events, menu_config.sort)
+        .into_iter()
+        .filter(|event| !menu_config.hide_past || !is_past_event(event, now))
+        .collect()
+}
+
+fn append_no_events_item(m: &Menu, markup: &str) {
+    let item = gtk::MenuItem::with_label("test");
+    let label = item.child().unwrap();
+    (label.downcast::<gtk::Label>()).unwrap().set_markup(markup);
+    m.append(&item);
+}
+
+fn sort_menu_events(events: &[Event], menu_sort: MenuSort) -> Vec<&Event> {
+    match menu_sort {
+        MenuSort::Chronological => events.iter().collect(),
+        MenuSort::UpcomingFirst => {
+            let now = Local::now();
+            // partition is stable so the chronological order is kept within both groups
+            let (past_events, other_events): (Vec<&Event>, Vec<&Event>) =
+                events.iter().partition(|event| is_past_event(event, &now));
+            other_events.into_iter().chain(past_events).collect()
+        }
+    }
+}
+
+/// Shows where an online meeting takes place, e.g. " (Zoom)", or nothing when there is no meeting URL
+fn meeting_provider_label(event: &Event) -> String {
+    match (&event.meeturl, &event.meeting_provider) {
+        (Some(_), Some(meeting_provider)) => format!(" ({})", meeting_provider),
+        _ => "".to_string(),
+    }
+}
+
+/// Appends the menu item for the event to the menu, followed by items for its dial-in and
+/// attachments. Returns whether the event is still running or upcoming.
+fn append_event_menu_items(m: &Menu, event: &Event, menu_config: MenuConfig) -> bool {
+    let mut upcoming = false;
+    let all_day = event.all_day;
+    let time_string = with_secondary_time(
+        format_event_time(event, menu_config.show_duration),
+        event,
+        menu_config.secondary_tz,
+    );
+    let meeturl_string = meeting_provider_label(event);
+    // a meeting with only one participant is just us, that is not worth showing
+    let participants_string = if menu_config.show_participants && event.num_participants > 1 {
+        format!(" 👥 {}", event.num_participants)
+    } else {
+        "".to_string()
+    };
+
+    // We need to actually create a menu item with a dummy label, then get that child
+    // element, cast it to an actual label and then modify its markup to make sure we get
+    // menu items that are left aligned but expand to fill horizontal space
+    // The first attempt to create an empty item and then add a label caused those items
+    // to have text that was only selectable/highlighted until the end of the text but not
+    // the end of the menu item
+    let item = gtk::MenuItem::with_label("Test");
+    let label = item.child().unwrap().downcast::<gtk::Label>().unwrap();
+    // we used to format this text with markup and uset set_markup but that causes potential
+    // escaping issues and we just default to plain text now
+    let now = Local::now();
+    let summary = match menu_config.max_label_len {
+        Some(max_label_len) => {
+            let truncated = truncate_with_ellipsis(&event.summary, max_label_len);
+            if truncated != event.summary {
+                // the label is cut off, so we make the full title available as a tooltip
+                item.set_tooltip_text(Some(&event.summary));
+            }
+            truncated
+        }
+        None => event.summary.clone(),
+    };
+    // meetings we organize are marked so they stand out, to-dos look like a checkbox
+    let summary = if event.todo {
+        format!("☐ {}", summary)
+    } else if event.is_organizer {
+        format!("★ {}", summary)
+    } else {
+        summary
+    };
+    let label_string = if all_day {
+        format!(
+            "{}: {}{}{}",
+            time_string, summary, meeturl_string, participants_string
+        )
+    } else if now < event.start_timestamp {
+        upcoming = true;
+        format!(
+            "◦ {}: {}{}{}",
+            time_string, summary, meeturl_string, participants_string
+        )
+    } else if is_running_event(event, &now) {
+        upcoming = true;
+        format!(
+            "• {}: {}{}{}",
+            time_string, summary, meeturl_string, participants_string
+        )
     } else {
-        for event in events {
-            let all_day = event.start_timestamp.time() == event.end_timestamp.time();
-            let time_string = if all_day {
-                "All Day".to_owned()
+        format!(
+            "✓ {}: {}{}{}",
+            time_string, summary, meeturl_string, participants_string
+        )
+    };
+
+    label.set_text(&label_string);
+    let new_event = (*event).clone();
+    if new_event.meeturl.is_some() {
+        item.connect_activate(move |_clicked_item| {
+            let meet_url = &new_event.meeturl.as_ref().unwrap();
+            open_provider_meeting(meet_url, new_event.meeting_provider.as_deref());
+        });
+    }
+    m.append(&item);
+    if let Some(dialin) = &event.dialin {
+        let dialin_item = gtk::MenuItem::with_label(&format!("    ☎ {}", dialin));
+        let tel_uri = dialin_to_tel_uri(dialin);
+        dialin_item.connect_activate(move |_clicked_item| {
+            open_meeting(&tel_uri);
+        });
+        m.append(&dialin_item);
+    }
+    if let Some(passcode) = &event.passcode {
+        // clicking the passcode copies it, that way it can be pasted when the client asks for it
+        let passcode_item = gtk::MenuItem::with_label(&format!("    🔑 Passcode {}", passcode));
+        let passcode = passcode.clone();
+        passcode_item.connect_activate(move |_clicked_item| {
+            gtk::Clipboard::get(&gtk::gdk::SELECTION_CLIPBOARD).set_text(&passcode);
+        });
+        m.append(&passcode_item);
+    }
+    if menu_config.show_calendar_name {
+        if let Some(calendar_name) = &event.calendar_name {
+            let calendar_item = gtk::MenuItem::with_label(&format!("    🗓 {}", calendar_name));
+            calendar_item.set_sensitive(false);
+            m.append(&calendar_item);
+        }
+    }
+    for comment in &event.comments {
+        let comment_item = gtk::MenuItem::with_label(&format!("    📝 {}", comment));
+        comment_item.set_sensitive(false);
+        m.append(&comment_item);
+    }
+    for attachment in &event.attachments {
+        let attachment_item = gtk::MenuItem::with_label(&format!("    📎 {}", attachment));
+        let attachment_url = attachment.clone();
+        attachment_item.connect_activate(move |_clicked_item| {
+            open_meeting(&attachment_url);
+        });
+        m.append(&attachment_item);
+    }
+    if event.nof_inline_attachments > 0 {
+        let inline_attachments_item = gtk::MenuItem::with_label(&format!(
+            "    📎 {} embedded attachment(s)",
+            event.nof_inline_attachments
+        ));
+        inline_attachments_item.set_sensitive(false);
+        m.append(&inline_attachments_item);
+    }
+    upcoming
+}
+
+/// Formats the events as a plain text agenda with one line per event, e.g. "09:00 Standup"
+fn format_agenda(events: &[Event]) -> String {
+    events
+        .iter()
+        .map(|event| {
+            if event.all_day {
+                format!("All Day {}", event.summary)
             } else {
                 format!(
-                    "{} - {}",
-                    &event.start_timestamp.format("%H:%M"),
-                    &event.end_timestamp.format("%H:%M")
+                    "{} {}",
+                    event.start_timestamp.format("%H:%M"),
+                    event.summary
                 )
-                .to_owned()
-            };
-            let meeturl_string = match &event.meeturl {
-                Some(_) => " (Zoom)",
-                None => "",
-            };
-
-            // We need to actually create a menu item with a dummy label, then get that child
-            // element, cast it to an actual label and then modify its markup to make sure we get
-            // menu items that are left aligned but expand to fill horizontal space
-            // The first attempt to create an empty item and then add a label caused those items
-            // to have text that was only selectable/highlighted until the end of the text but not
-            // the end of the menu item
-            let item = gtk::MenuItem::with_label("Test");
-            let label = item.child().unwrap().downcast::<gtk::Label>().unwrap();
-            // we used to format this text with markup and uset set_markup but that causes potential
-            // escaping issues and we just default to plain text now
-            let now = Local::now();
-            let label_string = if all_day {
-                format!("{}: {}{}", time_string, &event.summary, meeturl_string)
-            } else if now < event.start_timestamp {
-                nof_upcoming_meetings += 1;
-                format!("◦ {}: {}{}", time_string, &event.summary, meeturl_string)
-            } else if now >= event.start_timestamp && now <= event.end_timestamp {
-                nof_upcoming_meetings += 1;
-                format!("• {}: {}{}", time_string, &event.summary, meeturl_string)
-            } else {
-                format!("✓ {}: {}{}", time_string, &event.summary, meeturl_string)
-            };
-
-            label.set_text(&label_string);
-            let new_event = (*event).clone();
-            if new_event.meeturl.is_some() {
-                item.connect_activate(move |_clicked_item| {
-                    let meet_url = &new_event.meeturl.as_ref().unwrap();
-                    open_meeting(meet_url);
-                });
             }
-            m.append(&item);
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// What the menu needs to pin and unpin meetings
+#[derive(Clone)]
+struct PinState {
+    pinned_meetings: Arc<Mutex<PinnedMeetings>>,
+    /// Set when the pinned meetings changed so that the background thread recalculates them
+    refresh_requested: Arc<AtomicBool>,
+}
+
+/// Returns the next occurrence of each of the pinned meetings that has not ended yet, sorted by
+/// start time
+fn get_next_pinned_occurrences(
+    events: &[Event],
+    pinned_uids: &HashSet<String>,
+    now: &DateTime<Local>,
+) -> Vec<Event> {
+    let mut next_occurrences: Vec<Event> = pinned_uids
+        .iter()
+        .filter_map(|uid| {
+            events
+                .iter()
+                .filter(|event| &event.uid == uid && event.end_timestamp > *now)
+                .min_by_key(|event| event.start_timestamp)
+                .cloned()
+        })
+        .collect();
+    next_occurrences.sort_by(|a, b| Ord::cmp(&a.start_timestamp, &b.start_timestamp));
+    next_occurrences
+}
+
+fn append_pinned_menu_items(m: &Menu, pinned_events: &[Event]) {
+    let header_item = gtk::MenuItem::with_label("📌 Pinned");
+    header_item.set_sensitive(false);
+    m.append(&header_item);
+    for pinned_event in pinned_events {
+        let item = gtk::MenuItem::with_label(&format!(
+            "    {}: {}{}",
+            pinned_event.start_timestamp.format("%a %H:%M"),
+            pinned_event.summary,
+            meeting_provider_label(pinned_event)
+        ));
+        if let Some(meeturl) = pinned_event.meeturl.clone() {
+            let provider = pinned_event.meeting_provider.clone();
+            item.connect_activate(move |_clicked_item| {
+                open_provider_meeting(&meeturl, provider.as_deref());
+            });
+        }
+        m.append(&item);
+    }
+    m.append(&gtk::SeparatorMenuItem::new());
+}
+
+/// Creates a submenu with a check item per meeting of today that pins or unpins it
+fn create_pin_menu(events: &[Event], pin_state: &PinState) -> Menu {
+    let pin_menu = gtk::Menu::new();
+    let mut seen_uids = HashSet::new();
+    // recurring meetings are pinned as a whole so we only need one item per UID
+    for event in events.iter().filter(|event| !event.uid.is_empty()) {
+        if !seen_uids.insert(event.uid.clone()) {
+            continue;
+        }
+        let pin_item = gtk::CheckMenuItem::with_label(&event.summary);
+        pin_item.set_active(
+            pin_state
+                .pinned_meetings
+                .lock()
+                .unwrap()
+                .is_pinned(&event.uid),
+        );
+        let uid = event.uid.clone();
+        let pin_state = pin_state.clone();
+        pin_item.connect_toggled(move |toggled_item| {
+            pin_state
+                .pinned_meetings
+                .lock()
+                .unwrap()
+                .set_pinned(&uid, toggled_item.is_active());
+            pin_state.refresh_requested.store(true, Ordering::SeqCst);
+        });
+        pin_menu.append(&pin_item);
+    }
+    pin_menu
+}
+
+/// Appends the holidays of today as disabled items so they look different from meetings
+fn append_holiday_menu_items(m: &Menu, holiday_events: &[Event]) {
+    for holiday_event in holiday_events {
+        let item = gtk::MenuItem::with_label(&format!("🎉 {}", holiday_event.summary));
+        item.set_sensitive(false);
+        m.append(&item);
+    }
+    m.append(&gtk::SeparatorMenuItem::new());
+}
+
+fn create_indicator_menu(
+    menu_day: MenuDay,
+    events: &[domain::Event],
+    pinned_events: &[domain::Event],
+    holiday_events: &[domain::Event],
+    indicator: &mut AppIndicator,
+    menu_config: MenuConfig,
+    pin_state: &PinState,
+    show_holidays: &Option<Arc<AtomicBool>>,
+) {
+    let mut m: Menu = gtk::Menu::new();
+    let mut nof_upcoming_meetings = 0;
+    if !pinned_events.is_empty() {
+        append_pinned_menu_items(&m, pinned_events);
+    }
+    let holidays_shown = show_holidays
+        .as_ref()
+        .map_or(false, |show_holidays| show_holidays.load(Ordering::SeqCst));
+    if holidays_shown && !holiday_events.is_empty() {
+        append_holiday_menu_items(&m, holiday_events);
+    }
+    let now = Local::now();
+    let menu_events = get_menu_events(events, menu_config, &now);
+    if events.is_empty() {
+        append_no_events_item(&m, &format!("<b>No Events {}</b>", menu_day.name()));
+    } else if menu_events.is_empty() {
+        append_no_events_item(&m, &format!("<b>No More Events {}</b>", menu_day.name()));
+    } else {
+        // the meetings of tomorrow could easily be mistaken for those of today
+        if menu_day == MenuDay::Tomorrow {
+            append_no_events_item(&m, "<b>Tomorrow</b>");
+        }
+        // In the compact menu all meetings that are over go into a submenu that takes the place
+        // of the first of those meetings
+        let past_events: Vec<&Event> = if menu_config.compact {
+            menu_events
+                .iter()
+                .filter(|event| is_past_event(event, &now))
+                .cloned()
+                .collect()
+        } else {
+            vec![]
+        };
+        let mut past_events_appended = false;
+        for event in menu_events {
+            if past_events.is_empty() || !is_past_event(event, &now) {
+                // events that are marked as free time are shown but they do not count as meetings
+                if append_event_menu_items(&m, event, menu_config) && event.busy {
+                    nof_upcoming_meetings += 1;
+                }
+            } else if !past_events_appended {
+                let past_events_menu = gtk::Menu::new();
+                for past_event in &past_events {
+                    append_event_menu_items(&past_events_menu, past_event, menu_config);
+                }
+                let past_events_item = gtk::MenuItem::with_label(&format!(
+                    "▸ {} earlier meeting(s)",
+                    past_events.len()
+                ));
+                past_events_item.set_submenu(Some(&past_events_menu));
+                m.append(&past_events_item);
+                past_events_appended = true;
+            }
         }
     }
+    if !events.is_empty() {
+        let agenda = format_agenda(events);
+        let copy_agenda_item =
+            gtk::MenuItem::with_label(&format!("Copy {}'s agenda", menu_day.name().to_lowercase()));
+        copy_agenda_item.connect_activate(move |_| {
+            gtk::Clipboard::get(&gtk::gdk::SELECTION_CLIPBOARD).set_text(&agenda);
+        });
+        m.append(&gtk::SeparatorMenuItem::new());
+        m.append(&copy_agenda_item);
+        let pin_item = gtk::MenuItem::with_label("Pin meetings");
+        pin_item.set_submenu(Some(&create_pin_menu(events, pin_state)));
+        m.append(&pin_item);
+    }
+    // the toggle is only there when a holiday calendar is configured
+    if let Some(show_holidays) = show_holidays {
+        let holidays_item = gtk::CheckMenuItem::with_label("Show holidays");
+        holidays_item.set_active(holidays_shown);
+        let show_holidays = show_holidays.clone();
+        let refresh_requested = pin_state.refresh_requested.clone();
+        holidays_item.connect_toggled(move |toggled_item| {
+            show_holidays.store(toggled_item.is_active(), Ordering::SeqCst);
+            refresh_requested.store(true, Ordering::SeqCst);
+        });
+        if events.is_empty() {
+            m.append(&gtk::SeparatorMenuItem::new());
+        }
+        m.append(&holidays_item);
+    }
     let mi = gtk::MenuItem::with_label("Quit");
     mi.connect_activate(|_| {
         gtk::main_quit();
@@ -212,96 +1044,436 @@ fn create_indicator_menu(events: &[domain::Event], indicator: &mut AppIndicator)
     indicator.set_menu(&mut m);
 }
 
+/// Running several instances side by side, for example for a work and a personal calendar,
+/// requires each of them to have their own `MEETERS_INSTANCE_NAME`. Since the name also determines
+/// where the configuration file is, it can only be set in the environment.
+fn get_instance_name() -> Option<String> {
+    std::env::var("MEETERS_INSTANCE_NAME").ok()
+}
+
+/// The identifier used for the indicator and the configuration directory, this is just
+/// "meeters" unless an instance name is set.
+fn instance_id() -> String {
+    match get_instance_name() {
+        Some(instance_name) => format!("meeters-{}", instance_name),
+        None => "meeters".to_string(),
+    }
+}
+
 fn get_config_directory() -> PathBuf {
-    ProjectDirs::from("net", "aggregat4", "meeters")
+    ProjectDirs::from("net", "aggregat4", &instance_id())
         .expect("Project directory must be available")
         .config_dir()
         .to_path_buf()
 }
 
+/// The configuration file can be explicitly specified with the `--config <path>` command line
+/// argument or the `MEETERS_CONFIG_FILE` environment variable, in that order of precedence. If
+/// neither is present we use the default location in the project configuration directory.
+fn get_explicit_config_file() -> Option<PathBuf> {
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(pos) = args.iter().position(|arg| arg == "--config") {
+        match args.get(pos + 1) {
+            Some(path) => return Some(PathBuf::from(path)),
+            None => panic!("The --config argument requires a path to a configuration file"),
+        }
+    }
+    std::env::var("MEETERS_CONFIG_FILE").ok().map(PathBuf::from)
+}
+
+/// Returns the UID passed with `--dump-event <UID>`, in that case we just print everything we know
+/// about that event and exit
+fn get_dump_event_uid() -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter()
+        .position(|arg| arg == "--dump-event")
+        .map(|pos| match args.get(pos + 1) {
+            Some(uid) => uid.clone(),
+            None => panic!("The --dump-event argument requires the UID of an event"),
+        })
+}
+
 fn load_config() -> std::io::Result<()> {
-    let config_file = get_config_directory().join("meeters_config.env");
+    let config_file = match get_explicit_config_file() {
+        Some(explicit_config_file) => {
+            if !explicit_config_file.exists() {
+                panic!(
+                    "The explicitly specified configuration file at {} does not exist",
+                    explicit_config_file.to_str().unwrap()
+                );
+            }
+            explicit_config_file
+        }
+        None => get_config_directory().join("meeters_config.env"),
+    };
     if !config_file.exists() {
         panic!(
             "Require the project configuration file to be present at {}",
             config_file.to_str().unwrap()
         );
     }
-    dotenvy::from_path(config_file).expect("Can not load configuration file meeters_config.env");
+    if let Err(e) = dotenvy::from_path(&config_file) {
+        panic!(
+            "Can not load configuration file {}: {}",
+            config_file.to_str().unwrap(),
+            e
+        );
+    }
     Ok(())
 }
 
-fn get_events_for_interval(
-    events: Vec<Event>,
-    start_time: DateTime<Tz>,
-    end_time: DateTime<Tz>,
-) -> Vec<Event> {
-    let mut filtered_events = events
-        .into_iter()
-        .filter(|e| {
-            // We check for events that are inside the interval OR overlap with the interval in some way
-            (e.start_timestamp > start_time && e.start_timestamp < end_time)
-                || (e.start_timestamp < start_time && e.end_timestamp > start_time)
-                || (e.start_timestamp < end_time && e.end_timestamp > end_time)
-        })
-        .collect::<Vec<_>>();
-    filtered_events.sort_by(|a, b| Ord::cmp(&a.start_timestamp, &b.start_timestamp));
-    filtered_events
+/// Free and tentative events are not that important so they get a low urgency, meetings we
+/// organize or that have a high priority are always critical. All other events use the
+/// configured default urgency.
+fn notification_urgency(event: &Event, default_urgency: Urgency) -> Urgency {
+    if !event.busy || event.tentative {
+        Urgency::Low
+    } else if event.is_organizer || (1..=4).contains(&event.priority) {
+        Urgency::Critical
+    } else {
+        default_urgency
+    }
+}
+
+/// Shows a notification without actions, failures are only logged
+fn show_simple_notification(backend: &dyn NotificationBackend, summary: String, body: String) {
+    let content = NotificationContent {
+        summary,
+        body,
+        icon: "appointment-new".to_string(),
+        urgency: Urgency::Normal,
+        actions: vec![],
+    };
+    if backend.show(&content, Box::new(|_| {})).is_err() {
+        println!("Could not show notification");
+    }
 }
 
-fn show_event_notification(event: Event) {
+fn show_event_notification(
+    backend: &dyn NotificationBackend,
+    event: Event,
+    default_urgency: Urgency,
+    dismissed_events: Arc<Mutex<EventLog>>,
+) {
     // println!("Event notification: {:?}", event);
     let summary_str = &format!(
         "{} - {}",
         event.start_timestamp.format("%H:%M"),
         event.summary
     );
-    let mut notification = Notification::new();
-    notification
-        .summary(summary_str)
-        .body(
-            &event
-                .meeturl
-                .clone()
-                .or_else(|| Some("No Zoom Meeting".to_string()))
-                .unwrap(),
-        )
-        // icons are standard freedesktop.org icon names, see https://specifications.freedesktop.org/icon-naming-spec/icon-naming-spec-latest.html
-        .icon("appointment-new")
-        // Critical urgency has to be manually dismissed (according to XDG spec), this seems like what we want?
-        .urgency(notify_rust::Urgency::Critical);
+    let mut body = event
+        .meeturl
+        .clone()
+        .or_else(|| Some("No Online Meeting".to_string()))
+        .unwrap();
+    if let Some(passcode) = &event.passcode {
+        body = format!("{}\nPasscode: {}", body, passcode);
+    }
+    let mut actions = vec![];
     // In case we have a meeting url we want to allow opening the meeting
-    if let Some(meeturl) = event.meeturl {
-        notification
-            .action(
-                &format!("{}{}", MEETERS_NOTIFICATION_ACTION_OPEN_MEETING, meeturl),
-                "Open Zoom Meeting",
-            )
-            .show()
-            .unwrap()
-            .wait_for_action(|action| {
-                if let Some(meeting) = action.strip_prefix(MEETERS_NOTIFICATION_ACTION_OPEN_MEETING)
-                {
-                    open_meeting(meeting);
-                }
-            });
+    if let Some(meeturl) = &event.meeturl {
+        actions.push((
+            format!("{}{}", MEETERS_NOTIFICATION_ACTION_OPEN_MEETING, meeturl),
+            format!(
+                "Open {} Meeting",
+                event.meeting_provider.as_deref().unwrap_or("Online")
+            ),
+        ));
+    }
+    // Dismissing makes sure we never notify for this particular event again
+    actions.push((
+        MEETERS_NOTIFICATION_ACTION_DISMISS.to_string(),
+        "Dismiss".to_string(),
+    ));
+    let content = NotificationContent {
+        summary: summary_str.to_string(),
+        body,
+        icon: "appointment-new".to_string(),
+        // Critical urgency has to be manually dismissed (according to XDG spec), this seems like what we want?
+        urgency: notification_urgency(&event, default_urgency),
+        actions,
+    };
+    let result = backend.show(
+        &content,
+        Box::new(move |action| {
+            if let Some(meeting) = action.strip_prefix(MEETERS_NOTIFICATION_ACTION_OPEN_MEETING) {
+                open_provider_meeting(meeting, event.meeting_provider.as_deref());
+                // we joined, so there is no need to remind us again when the meeting starts
+                dismissed_events
+                    .lock()
+                    .unwrap()
+                    .add(dismissal_key(&event), &event);
+            } else if action == MEETERS_NOTIFICATION_ACTION_DISMISS {
+                dismissed_events
+                    .lock()
+                    .unwrap()
+                    .add(dismissal_key(&event), &event);
+            }
+        }),
+    );
+    if result.is_err() {
+        println!("Could not show notification");
+    }
+}
+
+/// Summarizes the meetings on the given day, like "You have 5 meetings today, first at 09:00".
+/// Only actual meetings count, so free, all-day and to-do entries are ignored.
+fn format_morning_summary(events: &[Event], today: NaiveDate) -> String {
+    let meetings: Vec<&Event> = events
+        .iter()
+        .filter(|event| {
+            event.busy
+                && !event.todo
+                && !event.all_day
+                && event.start_timestamp.naive_local().date() == today
+        })
+        .collect();
+    let first_start = match meetings.iter().map(|event| event.start_timestamp).min() {
+        Some(first_start) => first_start,
+        None => return "You have no meetings today".to_string(),
+    };
+    let nof_video_meetings = meetings
+        .iter()
+        .filter(|event| event.meeturl.is_some())
+        .count();
+    let summary = if meetings.len() == 1 {
+        format!(
+            "You have 1 meeting today at {}",
+            first_start.format("%H:%M")
+        )
+    } else {
+        format!(
+            "You have {} meetings today, first at {}",
+            meetings.len(),
+            first_start.format("%H:%M")
+        )
+    };
+    if nof_video_meetings > 0 {
+        format!("{} ({} with a video link)", summary, nof_video_meetings)
+    } else {
+        summary
+    }
+}
+
+fn show_morning_summary_notification(backend: &dyn NotificationBackend, summary: String) {
+    show_simple_notification(backend, "Good morning".to_string(), summary);
+}
+
+fn show_event_end_notification(backend: &dyn NotificationBackend, event: Event) {
+    show_simple_notification(
+        backend,
+        format!("Meeting ending: {}", event.summary),
+        format!(
+            "{} - {}",
+            event.start_timestamp.format("%H:%M"),
+            event.end_timestamp.format("%H:%M")
+        ),
+    );
+}
+
+fn show_event_updated_notification(backend: &dyn NotificationBackend, event: Event) {
+    show_simple_notification(
+        backend,
+        format!("Meeting updated: {}", event.summary),
+        format!(
+            "{} - {}\n{}",
+            event.start_timestamp.format("%H:%M"),
+            event.end_timestamp.format("%H:%M"),
+            event.location
+        ),
+    );
+}
+
+/// Parses the minutes past the hour at which to poll the calendar. This is either a list like
+/// "0,30", a step like "*/15" or a cron expression like "0,30 * * * *" where only the minute
+/// field may be restricted.
+fn parse_poll_minutes(spec: &str) -> Result<Vec<u32>, String> {
+    let mut fields = spec.split_whitespace();
+    let minute_field = fields.next().ok_or("The schedule is empty")?;
+    if fields.any(|field| field != "*") {
+        return Err(format!(
+            "Only the minute field of '{}' may be restricted",
+            spec
+        ));
+    }
+    let mut minutes = vec![];
+    for part in minute_field.split(',') {
+        if part == "*" {
+            minutes.extend(0..60);
+        } else if let Some(step) = part.strip_prefix("*/") {
+            match step.parse::<u32>() {
+                Ok(step) if step > 0 => minutes.extend((0..60).step_by(step as usize)),
+                _ => return Err(format!("Invalid step '{}' in '{}'", step, spec)),
+            }
+        } else {
+            match part.parse::<u32>() {
+                Ok(minute) if minute < 60 => minutes.push(minute),
+                _ => return Err(format!("Invalid minute '{}' in '{}'", part, spec)),
+            }
+        }
+    }
+    minutes.sort_unstable();
+    minutes.dedup();
+    Ok(minutes)
+}
+
+/// The first full minute after now whose minute past the hour is one of the poll minutes
+fn next_poll_time(now: &DateTime<Local>, poll_minutes: &[u32]) -> DateTime<Local> {
+    let mut candidate = now.with_second(0).unwrap().with_nanosecond(0).unwrap();
+    // at most an hour away since the poll minutes are never empty and all below 60
+    loop {
+        candidate = candidate + chrono::Duration::minutes(1);
+        if poll_minutes.contains(&candidate.minute()) {
+            return candidate;
+        }
+    }
+}
+
+/// The configured polling interval wins over the refresh interval that the calendar asks for,
+/// which in turn is never shorter than a minute so a misconfigured feed can not make us hammer
+/// the server
+fn polling_interval_ms(
+    configured_interval_ms: Option<u128>,
+    calendar_refresh_interval: Option<chrono::Duration>,
+) -> u128 {
+    configured_interval_ms
+        .or_else(|| {
+            calendar_refresh_interval.map(|interval| {
+                (interval.num_milliseconds() as u128).max(MIN_CALENDAR_REFRESH_INTERVAL_MS)
+            })
+        })
+        .unwrap_or(DEFAULT_POLLING_INTERVAL_MS)
+}
+
+/// The lead times in seconds before the start of the event at which we notify about it, from
+/// the shortest to the longest. Alarms with the same lead time as the configured warning time
+/// only give one notification.
+fn notification_lead_times(
+    event: &Event,
+    warning_time_seconds: i64,
+    notify_at_alarms: bool,
+) -> Vec<i64> {
+    let mut lead_times = vec![warning_time_seconds];
+    if notify_at_alarms {
+        lead_times.extend(&event.alarm_lead_seconds);
+    }
+    lead_times.sort_unstable();
+    lead_times.dedup();
+    lead_times
+}
+
+/// The events we need to notify about now, each with the lead time it is notified for. An event is
+/// due when it starts within one of its lead times and `is_handled` says we did not notify about
+/// it for that lead time yet and it was not dismissed. Every event is checked so that an event we
+/// already notified about does not hold back the notifications of later events.
+fn due_notifications<'a, F>(
+    events: &'a [Event],
+    now: &DateTime<Local>,
+    warning_time_seconds: i64,
+    notify_at_alarms: bool,
+    min_duration_minutes: i64,
+    is_handled: F,
+) -> Vec<(&'a Event, i64)>
+where
+    F: Fn(&Event, i64) -> bool,
+{
+    events
+        .iter()
+        .filter_map(|event| {
+            let seconds_until_start = event
+                .start_timestamp
+                .signed_duration_since(*now)
+                .num_seconds();
+            // to-dos are just reminders of a due date, we never notify about them
+            if event.todo
+                || seconds_until_start <= 0
+                || !is_long_enough_to_notify(event, min_duration_minutes)
+            {
+                return None;
+            }
+            // only the shortest lead time that applies counts, so when we only see the
+            // event shortly before it starts we do not notify for every longer lead time
+            notification_lead_times(event, warning_time_seconds, notify_at_alarms)
+                .into_iter()
+                .find(|lead_seconds| seconds_until_start <= *lead_seconds)
+                .filter(|lead_seconds| !is_handled(event, *lead_seconds))
+                .map(|lead_seconds| (event, lead_seconds))
+        })
+        .collect()
+}
+
+/// Very short events are usually reminders or placeholders and not real meetings
+fn is_long_enough_to_notify(event: &Event, min_duration_minutes: i64) -> bool {
+    (event.end_timestamp - event.start_timestamp).num_minutes() >= min_duration_minutes
+}
+
+/// Whether the event is a newer revision of the previously seen event. The SEQUENCE is the
+/// primary indicator for changes, when it did not change we fall back to LAST-MODIFIED.
+fn is_newer_revision(event: &Event, previous_event: &Event) -> bool {
+    if event.sequence != previous_event.sequence {
+        event.sequence > previous_event.sequence
     } else {
-        if let Err(_) = notification.show() {
-            println!("Could not show notification");
+        match (event.last_modified, previous_event.last_modified) {
+            (Some(last_modified), Some(previous_last_modified)) => {
+                last_modified > previous_last_modified
+            }
+            _ => false,
         }
     }
 }
 
 /// Time between two ical calendar download in milliseconds
 const DEFAULT_POLLING_INTERVAL_MS: u128 = 2 * 60 * 1000;
+/// The shortest polling interval we accept from the refresh interval of a calendar
+const MIN_CALENDAR_REFRESH_INTERVAL_MS: u128 = 60 * 1000;
 /// The amount of time in seconds we want to be warned before the meeting starts
 const DEFAULT_EVENT_WARNING_TIME_SECONDS: i64 = 60;
+/// The number of characters of an event description we keep
+const DEFAULT_MAX_DESCRIPTION_LEN: usize = 4096;
+/// How long after the end of a meeting we still notify that it ended, this needs to be larger than
+/// the interval of the background loop
+const EVENT_END_NOTIFICATION_GRACE_SECONDS: i64 = 30;
+/// When this much time passed between two iterations of the background loop we assume that the
+/// computer was suspended
+const SUSPEND_DETECTION_THRESHOLD_MS: u128 = 60 * 1000;
+/// How long we wait after resuming from a suspend before downloading the calendar
+const RESUME_DOWNLOAD_DELAY_MS: u128 = 10 * 1000;
+/// How long after the start of a meeting we still join it automatically, this needs to be larger
+/// than the interval of the background loop
+const AUTOJOIN_GRACE_SECONDS: i64 = 30;
+/// How long after the start of a meeting we still repeat a notification that was not acted upon
+const RENOTIFY_GRACE_SECONDS: i64 = 60;
 /// This is a prefix used to identify notification actions that are meant to open a meeting
 const MEETERS_NOTIFICATION_ACTION_OPEN_MEETING: &str = "meeters_open_meeting:";
+/// This is the identifier of the notification action that dismisses an event
+const MEETERS_NOTIFICATION_ACTION_DISMISS: &str = "meeters_dismiss";
 
 enum CalendarMessages {
-    TodayEvents(Vec<Event>),
+    /// The events of the day shown in the menu, today or after the rollover hour tomorrow
+    TodayEvents(MenuDay, Vec<Event>),
+    /// The next occurrences of the pinned meetings, sent before the events of today
+    PinnedEvents(Vec<Event>),
+    /// The holidays of the day shown in the menu, sent before the events of today and also when
+    /// our own calendar can not be fetched
+    HolidayEvents(Vec<Event>),
     EventNotification(Event),
+    EventEndNotification(Event),
+    EventUpdatedNotification(Event),
+    AutoJoin(Event),
+    MorningSummary(String),
+}
+
+/// Reads a configuration value that may be a secret. If a property with the name `<name>_FILE` is
+/// present, the value is read from the file it points to. Otherwise the value of the property
+/// `<name>` itself is used.
+fn get_secret_config(name: &str) -> Option<String> {
+    match dotenvy::var(format!("{}_FILE", name)) {
+        Ok(path) => match fs::read_to_string(&path) {
+            Ok(contents) => Some(contents.trim().to_string()),
+            Err(e) => panic!("Can not read {}_FILE at {}: {}", name, path, e),
+        },
+        Err(_) => dotenvy::var(name).ok(),
+    }
 }
 
 fn default_tz(_: dotenvy::Error) -> Result<String, dotenvy::Error> {
@@ -317,23 +1489,302 @@ fn main() -> std::io::Result<()> {
     let local_tz: Tz = local_tz_iana
         .parse()
         .expect("Expecting to be able to parse the local timezone, instead got an error");
-    let config_ical_url = dotenvy::var("MEETERS_ICAL_URL")
-        .expect("Expecting a configuration property with name MEETERS_ICAL_URL");
+    let config_ical_url = get_secret_config("MEETERS_ICAL_URL").expect(
+        "Expecting a configuration property with name MEETERS_ICAL_URL or MEETERS_ICAL_URL_FILE",
+    );
+    let config_ical_credentials: Option<(String, String)> = match (
+        dotenvy::var("MEETERS_ICAL_USERNAME").ok(),
+        get_secret_config("MEETERS_ICAL_PASSWORD"),
+    ) {
+        (Some(username), Some(password)) => Some((username, password)),
+        (None, None) => None,
+        _ => panic!("Basic authentication requires both MEETERS_ICAL_USERNAME and MEETERS_ICAL_PASSWORD (or MEETERS_ICAL_PASSWORD_FILE)"),
+    };
+    let http_agent = match (
+        dotenvy::var("MEETERS_TLS_CLIENT_CERT"),
+        dotenvy::var("MEETERS_TLS_CLIENT_KEY"),
+    ) {
+        (Ok(cert_path), Ok(key_path)) => ureq::AgentBuilder::new()
+            .tls_config(
+                load_client_tls_config(&cert_path, &key_path)
+                    .unwrap_or_else(|e| panic!("Can not use the TLS client certificate: {}", e)),
+            )
+            .build(),
+        (Err(_), Err(_)) => ureq::agent(),
+        _ => panic!("A TLS client certificate requires both MEETERS_TLS_CLIENT_CERT and MEETERS_TLS_CLIENT_KEY"),
+    };
+    let config_holiday_ical_url = get_secret_config("MEETERS_HOLIDAY_ICAL_URL");
+    let config_user_agent = dotenvy::var("MEETERS_USER_AGENT")
+        .unwrap_or_else(|_| format!("meeters/{}", env!("CARGO_PKG_VERSION")));
     let config_show_event_notification: bool = match dotenvy::var("MEETERS_EVENT_NOTIFICATION") {
         Ok(val) => val.parse::<bool>().expect(
             "Value for MEETERS_EVENT_NOTIFICATION configuration parameter must be a boolean",
         ),
         Err(_) => true,
     };
-    let config_polling_interval_ms: u128 = match dotenvy::var("MEETERS_POLLING_INTERVAL_MS") {
-        Ok(val) => val.parse::<u128>().expect("MEETERS_POLLING_INTERVAL_MS must be a positive integer expressing the polling interval in milliseconds"),
-        Err(_) => DEFAULT_POLLING_INTERVAL_MS
+    let config_polling_interval_ms: Option<u128> = match dotenvy::var("MEETERS_POLLING_INTERVAL_MS") {
+        Ok(val) => Some(val.parse::<u128>().expect("MEETERS_POLLING_INTERVAL_MS must be a positive integer expressing the polling interval in milliseconds")),
+        Err(_) => None
     };
     let config_event_warning_time_seconds: i64 = match dotenvy::var("MEETERS_EVENT_WARNING_TIME_SECONDS") {
         Ok(val) => val.parse::<i64>().expect("MEETERS_EVENT_WARNING_TIME_SECONDS must be a positive integer expressing the polling interval in seconds"),
         Err(_) => DEFAULT_EVENT_WARNING_TIME_SECONDS
     };
+    let config_poll_minutes: Option<Vec<u32>> = match dotenvy::var("MEETERS_POLL_CRON") {
+        Ok(val) => Some(parse_poll_minutes(&val).unwrap_or_else(|e| {
+            panic!(
+                "MEETERS_POLL_CRON must be a list of minutes like 0,30 or a cron expression: {}",
+                e
+            )
+        })),
+        Err(_) => None,
+    };
+    let config_renotify_at_start: bool = match dotenvy::var("MEETERS_RENOTIFY_AT_START") {
+        Ok(val) => val.parse::<bool>().expect(
+            "Value for MEETERS_RENOTIFY_AT_START configuration parameter must be a boolean",
+        ),
+        Err(_) => false,
+    };
+    let config_notify_at_alarms: bool = match dotenvy::var("MEETERS_NOTIFY_AT_ALARMS") {
+        Ok(val) => val
+            .parse::<bool>()
+            .expect("Value for MEETERS_NOTIFY_AT_ALARMS configuration parameter must be a boolean"),
+        Err(_) => false,
+    };
+    let config_notify_meeting_end: bool = match dotenvy::var("MEETERS_NOTIFY_MEETING_END") {
+        Ok(val) => val.parse::<bool>().expect(
+            "Value for MEETERS_NOTIFY_MEETING_END configuration parameter must be a boolean",
+        ),
+        Err(_) => false,
+    };
+    let config_min_notify_duration_minutes: i64 =
+        match dotenvy::var("MEETERS_MIN_NOTIFY_DURATION_MINUTES") {
+            Ok(val) => val.parse::<u32>().expect(
+                "MEETERS_MIN_NOTIFY_DURATION_MINUTES must be a positive integer expressing the minimum duration in minutes of meetings to notify about",
+            ) as i64,
+            Err(_) => 0,
+        };
+    let config_notification_urgency: Urgency = match dotenvy::var("MEETERS_NOTIFICATION_URGENCY") {
+        Ok(val) => Urgency::try_from(val.as_str())
+            .expect("MEETERS_NOTIFICATION_URGENCY must be one of low, normal or critical"),
+        Err(_) => Urgency::Critical,
+    };
+    let notification_backend: Box<dyn NotificationBackend> = match dotenvy::var(
+        "MEETERS_NOTIFICATION_BACKEND",
+    ) {
+        Ok(val) => match val.as_str() {
+            "auto" => Box::new(AutoBackend),
+            "dbus" => Box::new(DbusBackend),
+            "notify-send" => {
+                println!("Using notify-send for notifications, meeting notifications will have no Open and Dismiss buttons");
+                Box::new(NotifySendBackend)
+            }
+            _ => panic!("MEETERS_NOTIFICATION_BACKEND must be one of auto, dbus or notify-send"),
+        },
+        Err(_) => Box::new(AutoBackend),
+    };
+    let config_http_port: Option<u16> = match dotenvy::var("MEETERS_HTTP_PORT") {
+        Ok(val) => Some(
+            val.parse::<u16>()
+                .expect("MEETERS_HTTP_PORT must be a valid port number"),
+        ),
+        Err(_) => None,
+    };
+    let config_notify_meeting_updates: bool = match dotenvy::var("MEETERS_NOTIFY_MEETING_UPDATES") {
+        Ok(val) => val.parse::<bool>().expect(
+            "Value for MEETERS_NOTIFY_MEETING_UPDATES configuration parameter must be a boolean",
+        ),
+        Err(_) => false,
+    };
+    let config_max_events: Option<usize> = match dotenvy::var("MEETERS_MAX_EVENTS") {
+        Ok(val) => Some(val.parse::<usize>().expect(
+            "MEETERS_MAX_EVENTS must be a positive integer expressing the maximum number of events to process",
+        )),
+        Err(_) => None,
+    };
+    let config_max_description_len: usize = match dotenvy::var("MEETERS_MAX_DESCRIPTION_LEN") {
+        Ok(val) => val.parse::<usize>().expect(
+            "MEETERS_MAX_DESCRIPTION_LEN must be a positive integer expressing the maximum number of characters of a description",
+        ),
+        Err(_) => DEFAULT_MAX_DESCRIPTION_LEN,
+    };
+    let config_hide_summary_patterns: Vec<Regex> = match dotenvy::var("MEETERS_HIDE_SUMMARY_PATTERNS") {
+        Ok(val) => val
+            .split(';')
+            .filter(|pattern| !pattern.is_empty())
+            .map(|pattern| {
+                Regex::new(pattern).unwrap_or_else(|e| {
+                    panic!(
+                        "MEETERS_HIDE_SUMMARY_PATTERNS contains an invalid regular expression '{}': {}",
+                        pattern, e
+                    )
+                })
+            })
+            .collect(),
+        Err(_) => vec![],
+    };
+    let config_show_todos: bool = match dotenvy::var("MEETERS_SHOW_TODOS") {
+        Ok(val) => val
+            .parse::<bool>()
+            .expect("Value for MEETERS_SHOW_TODOS configuration parameter must be a boolean"),
+        Err(_) => false,
+    };
+    let config_unknown_tz_policy = match dotenvy::var("MEETERS_UNKNOWN_TZ_POLICY") {
+        Ok(val) => match val.as_str() {
+            "local" => meeters_ical::UnknownTzPolicy::Local,
+            "utc" => meeters_ical::UnknownTzPolicy::Utc,
+            "error" => meeters_ical::UnknownTzPolicy::Error,
+            _ => panic!("MEETERS_UNKNOWN_TZ_POLICY must be one of local, utc or error"),
+        },
+        Err(_) => meeters_ical::UnknownTzPolicy::Local,
+    };
+    let config_debug_timezones: bool = match dotenvy::var("MEETERS_DEBUG_TIMEZONES") {
+        Ok(val) => val
+            .parse::<bool>()
+            .expect("Value for MEETERS_DEBUG_TIMEZONES configuration parameter must be a boolean"),
+        Err(_) => false,
+    };
+    let extract_options = meeters_ical::ExtractOptions {
+        max_events: config_max_events,
+        preferred_language: dotenvy::var("MEETERS_PREFERRED_LANGUAGE").ok(),
+        user_email: dotenvy::var("MEETERS_USER_EMAIL").ok(),
+        hide_summary_patterns: config_hide_summary_patterns,
+        include_todos: config_show_todos,
+        unknown_tz_policy: config_unknown_tz_policy,
+        debug_timezones: config_debug_timezones,
+        max_description_len: Some(config_max_description_len),
+    };
+    if let Some(uid) = get_dump_event_uid() {
+        match get_ical(
+            &http_agent,
+            &config_ical_url,
+            &config_ical_credentials,
+            &config_user_agent,
+        )
+        .and_then(|text| meeters_ical::dump_event(&text, &uid, &local_tz, &extract_options))
+        {
+            Ok(dump) => println!("{}", dump),
+            Err(e) => eprintln!("Could not dump event {}: {}", uid, e),
+        }
+        return Ok(());
+    }
+    let config_day_rollover_hour: Option<u32> = match dotenvy::var("MEETERS_DAY_ROLLOVER_HOUR") {
+        Ok(val) => match val.parse::<u32>() {
+            Ok(hour) if hour <= 23 => Some(hour),
+            _ => panic!("MEETERS_DAY_ROLLOVER_HOUR must be an hour between 0 and 23 after which the menu shows the next day"),
+        },
+        Err(_) => None,
+    };
+    let config_morning_summary_hour: Option<u32> = match dotenvy::var("MEETERS_MORNING_SUMMARY_HOUR") {
+        Ok(val) => match val.parse::<u32>() {
+            Ok(hour) if hour <= 23 => Some(hour),
+            _ => panic!("MEETERS_MORNING_SUMMARY_HOUR must be an hour between 0 and 23 at which to show a summary of the day"),
+        },
+        Err(_) => None,
+    };
+    let config_autojoin_seconds: Option<i64> = match dotenvy::var("MEETERS_AUTOJOIN_SECONDS") {
+        Ok(val) => Some(val.parse::<u32>().expect("MEETERS_AUTOJOIN_SECONDS must be a positive integer expressing the time in seconds before a meeting to automatically join it") as i64),
+        Err(_) => None,
+    };
+    let config_menu_sort: MenuSort = match dotenvy::var("MEETERS_MENU_SORT") {
+        Ok(val) => match val.as_str() {
+            "chronological" => MenuSort::Chronological,
+            "upcoming-first" => MenuSort::UpcomingFirst,
+            _ => panic!(
+                "Value for MEETERS_MENU_SORT configuration parameter must be 'chronological' or 'upcoming-first'"
+            ),
+        },
+        Err(_) => MenuSort::Chronological,
+    };
+    let config_tray_icon_mode: TrayIconMode = match dotenvy::var("MEETERS_TRAY_ICON") {
+        Ok(val) => match val.as_str() {
+            "auto" => TrayIconMode::Auto,
+            "always" => TrayIconMode::Always,
+            "never" => TrayIconMode::Never,
+            _ => panic!(
+                "Value for MEETERS_TRAY_ICON configuration parameter must be 'auto', 'always' or 'never'"
+            ),
+        },
+        Err(_) => TrayIconMode::Auto,
+    };
+    let config_compact_menu: bool = match dotenvy::var("MEETERS_COMPACT_MENU") {
+        Ok(val) => val
+            .parse::<bool>()
+            .expect("Value for MEETERS_COMPACT_MENU configuration parameter must be a boolean"),
+        Err(_) => false,
+    };
+    let config_show_duration: bool = match dotenvy::var("MEETERS_SHOW_DURATION") {
+        Ok(val) => val
+            .parse::<bool>()
+            .expect("Value for MEETERS_SHOW_DURATION configuration parameter must be a boolean"),
+        Err(_) => false,
+    };
+    let config_show_participants: bool = match dotenvy::var("MEETERS_SHOW_PARTICIPANTS") {
+        Ok(val) => val.parse::<bool>().expect(
+            "Value for MEETERS_SHOW_PARTICIPANTS configuration parameter must be a boolean",
+        ),
+        Err(_) => false,
+    };
+    let config_menu_max_label_len: Option<usize> = match dotenvy::var("MEETERS_MENU_MAX_LABEL_LEN") {
+        Ok(val) => Some(val.parse::<usize>().expect(
+            "MEETERS_MENU_MAX_LABEL_LEN must be a positive integer expressing the maximum number of characters of a meeting title in the menu",
+        )),
+        Err(_) => None,
+    };
+    let config_menu_hide_past: bool = match dotenvy::var("MEETERS_MENU_HIDE_PAST") {
+        Ok(val) => val
+            .parse::<bool>()
+            .expect("Value for MEETERS_MENU_HIDE_PAST configuration parameter must be a boolean"),
+        Err(_) => false,
+    };
+    let config_menu_show_calendar_name: bool = match dotenvy::var("MEETERS_MENU_SHOW_CALENDAR_NAME")
+    {
+        Ok(val) => val.parse::<bool>().expect(
+            "Value for MEETERS_MENU_SHOW_CALENDAR_NAME configuration parameter must be a boolean",
+        ),
+        Err(_) => false,
+    };
+    let config_secondary_tz: Option<Tz> = match dotenvy::var("MEETERS_SECONDARY_TIMEZONE") {
+        Ok(val) => Some(val.parse().expect(
+            "MEETERS_SECONDARY_TIMEZONE must be an IANA timezone identifier like America/New_York",
+        )),
+        Err(_) => None,
+    };
+    let menu_config = MenuConfig {
+        sort: config_menu_sort,
+        compact: config_compact_menu,
+        show_duration: config_show_duration,
+        show_participants: config_show_participants,
+        max_label_len: config_menu_max_label_len,
+        secondary_tz: config_secondary_tz,
+        hide_past: config_menu_hide_past,
+        show_calendar_name: config_menu_show_calendar_name,
+    };
     println!("Local Timezone configured as {}", local_tz_iana.clone());
+    let dismissed_events = Arc::new(Mutex::new(EventLog::load(
+        get_config_directory().join("dismissed_events"),
+    )));
+    let notification_dismissed_events = dismissed_events.clone();
+    let pin_state = PinState {
+        pinned_meetings: Arc::new(Mutex::new(PinnedMeetings::load(
+            get_config_directory().join("pinned_meetings"),
+        ))),
+        refresh_requested: Arc::new(AtomicBool::new(false)),
+    };
+    let background_pin_state = pin_state.clone();
+    let show_holidays: Option<Arc<AtomicBool>> = config_holiday_ical_url
+        .as_ref()
+        .map(|_| Arc::new(AtomicBool::new(true)));
+    // today's events and all known events as served by the optional agenda endpoints
+    let agenda_events: Arc<Mutex<Vec<Event>>> = Arc::new(Mutex::new(vec![]));
+    let calendar_events: Arc<Mutex<Vec<Event>>> = Arc::new(Mutex::new(vec![]));
+    if let Some(http_port) = config_http_port {
+        agenda_server::start_agenda_server(
+            http_port,
+            agenda_events.clone(),
+            calendar_events.clone(),
+        );
+    }
     // magic incantation for gtk
     gtk::init().unwrap();
     // I can't get styles to work in appindicators
@@ -348,27 +1799,100 @@ fn main() -> std::io::Result<()> {
     // );
     // set up our widgets
     let mut indicator = create_indicator();
-    create_indicator_menu(&[], &mut indicator);
+    if !should_show_tray_icon(config_tray_icon_mode) {
+        indicator.set_status(AppIndicatorStatus::Passive);
+    }
+    create_indicator_menu(
+        MenuDay::Today,
+        &[],
+        &[],
+        &[],
+        &mut indicator,
+        menu_config,
+        &pin_state,
+        &show_holidays,
+    );
 
     // Create a message passing channel so we can communicate safely with the main GUI thread from our worker thread
     // let (status_sender, status_receiver) = glib::MainContext::channel::<String>(glib::PRIORITY_DEFAULT);
     let (events_sender, events_receiver) =
         glib::MainContext::channel::<Result<CalendarMessages, ()>>(glib::PRIORITY_DEFAULT);
+    // whether we ever got the calendar, if so the menu still shows the last known events on errors
+    let mut has_calendar_data = false;
+    let mut pinned_events: Vec<Event> = vec![];
+    let mut holiday_events: Vec<Event> = vec![];
+    // the last known events of the menu, kept so that new holidays can be shown with them
+    let mut menu_day = MenuDay::Today;
+    let mut menu_events: Vec<Event> = vec![];
     events_receiver.attach(None, move |event_result| {
         match event_result {
-            Ok(TodayEvents(events)) => {
-                if events.is_empty() {
-                    create_indicator_menu(&[], &mut indicator);
-                } else {
-                    create_indicator_menu(&events, &mut indicator);
-                }
+            Ok(PinnedEvents(events)) => pinned_events = events,
+            Ok(HolidayEvents(events)) => {
+                // the holidays arrive before our own calendar which may fail to download, so
+                // we show them right away
+                holiday_events = events;
+                create_indicator_menu(
+                    menu_day,
+                    &menu_events,
+                    &pinned_events,
+                    &holiday_events,
+                    &mut indicator,
+                    menu_config,
+                    &pin_state,
+                    &show_holidays,
+                );
+            }
+            Ok(TodayEvents(day, events)) => {
+                has_calendar_data = true;
+                menu_day = day;
+                menu_events = events;
+                create_indicator_menu(
+                    menu_day,
+                    &menu_events,
+                    &pinned_events,
+                    &holiday_events,
+                    &mut indicator,
+                    menu_config,
+                    &pin_state,
+                    &show_holidays,
+                );
             }
             Ok(EventNotification(event)) => {
                 if config_show_event_notification {
-                    show_event_notification(event);
+                    show_event_notification(
+                        notification_backend.as_ref(),
+                        event,
+                        config_notification_urgency,
+                        notification_dismissed_events.clone(),
+                    );
+                }
+            }
+            Ok(AutoJoin(event)) => {
+                if let Some(meeturl) = &event.meeturl {
+                    println!("Automatically joining meeting {}", event.summary);
+                    open_provider_meeting(meeturl, event.meeting_provider.as_deref());
+                }
+            }
+            Ok(EventUpdatedNotification(event)) => {
+                if config_notify_meeting_updates {
+                    show_event_updated_notification(notification_backend.as_ref(), event);
+                }
+            }
+            Ok(EventEndNotification(event)) => {
+                if config_notify_meeting_end {
+                    show_event_end_notification(notification_backend.as_ref(), event);
+                }
+            }
+            Ok(MorningSummary(summary)) => {
+                show_morning_summary_notification(notification_backend.as_ref(), summary)
+            }
+            Err(_) => {
+                if has_calendar_data {
+                    set_stale_icon(&mut indicator);
+                } else {
+                    set_error_icon(&mut indicator);
                 }
             }
-            Err(_) => set_error_icon(&mut indicator),
         }
         glib::Continue(true)
     });
@@ -378,38 +1902,173 @@ fn main() -> std::io::Result<()> {
     thread::spawn(move || {
         let mut last_download_time = 0;
         let mut last_events: Vec<Event> = vec![];
-        let mut last_notification_start_time: Option<DateTime<Tz>> = None;
+        // expanding all recurrences is expensive so we only do it when a calendar changed
+        let mut extraction_cache = meeters_ical::ExtractionCache::default();
+        let mut holiday_extraction_cache = meeters_ical::ExtractionCache::default();
+        // the newest revision of each event we know about by UID, used to detect updated events
+        let mut known_revisions: HashMap<String, Event> = HashMap::new();
+        // we remember which notifications we have shown on disk so that a restart just before a
+        // meeting does not notify about it again
+        let mut notified_events = EventLog::load(get_config_directory().join("notified_events"));
+        let mut last_notification_end_time: Option<DateTime<Tz>> = None;
+        let mut last_autojoin_start_time: Option<DateTime<Tz>> = None;
+        // the morning summary is only shown once per day and only when we know today's events
+        let mut last_morning_summary_date: Option<NaiveDate> = None;
+        let mut has_today_events = false;
+        let mut last_loop_time: u128 = 0;
+        // when we detect that we resumed from a suspend we download again soon after
+        let mut resume_download_time: Option<u128> = None;
+        // with a poll schedule we download at fixed minutes past the hour instead of an interval
+        let mut scheduled_download_time: Option<u128> = None;
         loop {
             let current_time = SystemTime::now()
                 .duration_since(UNIX_EPOCH)
                 .expect("Time must flow")
                 .as_millis();
+            // The loop runs every few seconds, when much more time has passed we were suspended.
+            // We give the network a moment to come back before downloading the calendar again,
+            // the first attempt right after waking up often fails because DNS is not ready yet.
+            if last_loop_time != 0 && current_time - last_loop_time > SUSPEND_DETECTION_THRESHOLD_MS
+            {
+                println!("Resumed from suspend, refreshing the calendar shortly");
+                resume_download_time = Some(current_time + RESUME_DOWNLOAD_DELAY_MS);
+            }
+            last_loop_time = current_time;
+            // pinning a meeting or toggling the holidays requests a refresh so that the menu is
+            // updated right away
             if last_download_time == 0
-                || current_time - last_download_time > config_polling_interval_ms
+                || background_pin_state
+                    .refresh_requested
+                    .swap(false, Ordering::SeqCst)
+                || resume_download_time.map_or(false, |time| current_time >= time)
+                || match scheduled_download_time {
+                    Some(time) => current_time >= time,
+                    None => {
+                        current_time - last_download_time
+                            > polling_interval_ms(
+                                config_polling_interval_ms,
+                                extraction_cache.refresh_interval(),
+                            )
+                    }
+                }
             {
                 last_download_time = current_time;
-                match get_ical(&config_ical_url)
-                    .and_then(|t| meeters_ical::extract_events(&t, &local_tz))
-                {
+                resume_download_time = None;
+                if let Some(poll_minutes) = &config_poll_minutes {
+                    scheduled_download_time = Some(
+                        next_poll_time(&Local::now(), poll_minutes).timestamp_millis() as u128,
+                    );
+                }
+                dismissed_events
+                    .lock()
+                    .unwrap()
+                    .prune(Local::now().timestamp());
+                notified_events.prune(Local::now().timestamp());
+                // let local_date = Local::now().date() - chrono::Duration::days(6);
+                let now = Local::now();
+                let start_of_day = |date: Date<Local>| {
+                    local_tz
+                        .ymd(date.year(), date.month(), date.day())
+                        .and_hms(0, 0, 0)
+                };
+                let today_start = start_of_day(now.date());
+                let today_end = start_of_day(now.date().succ());
+                // After the rollover hour the menu shows tomorrow, we keep notifying about
+                // the meetings that remain today though
+                let (menu_day, menu_start, menu_end) =
+                    if config_day_rollover_hour.map_or(false, |hour| now.hour() >= hour) {
+                        (
+                            MenuDay::Tomorrow,
+                            today_end,
+                            start_of_day(now.date().succ().succ()),
+                        )
+                    } else {
+                        (MenuDay::Today, today_start, today_end)
+                    };
+                // Holidays never end up in last_events, that way they are never notified about.
+                // They are independent of our own calendar, so they are parsed without our
+                // filters and still shown when our calendar can not be fetched. When the holiday
+                // calendar can not be fetched we keep the last ones.
+                if let Some(holiday_ical_url) = &config_holiday_ical_url {
+                    match get_ical(&http_agent, holiday_ical_url, &None, &config_user_agent)
+                        .and_then(|t| {
+                            holiday_extraction_cache.extract_events(
+                                &t,
+                                &local_tz,
+                                &meeters_ical::ExtractOptions::default(),
+                            )
+                        }) {
+                        Ok(holiday_events) => events_sender
+                            .send(Ok(HolidayEvents(holidays_in_range(
+                                &holiday_events,
+                                menu_start,
+                                menu_end,
+                            ))))
+                            .expect("Channel should be sendable"),
+                        Err(e) => eprintln!("Error getting holidays: {}", e),
+                    }
+                }
+                match download_events(
+                    &http_agent,
+                    &config_ical_url,
+                    &config_ical_credentials,
+                    &config_user_agent,
+                    &local_tz,
+                    &extract_options,
+                    &mut extraction_cache,
+                ) {
                     Ok(events) => {
                         println!("Successfully got {:?} events", events.len());
-                        // let local_date = Local::now().date() - chrono::Duration::days(6);
-                        let local_date = Local::now().date();
-                        let today_start = local_tz
-                            .ymd(local_date.year(), local_date.month(), local_date.day())
-                            .and_hms(0, 0, 0);
-                        let today_end = local_tz
-                            .ymd(local_date.year(), local_date.month(), local_date.day())
-                            .and_hms(23, 59, 59);
-                        let today_events = get_events_for_interval(events, today_start, today_end);
+                        if config_http_port.is_some() {
+                            *calendar_events.lock().unwrap() = events.clone();
+                        }
+                        let pinned_events = get_next_pinned_occurrences(
+                            &events,
+                            background_pin_state.pinned_meetings.lock().unwrap().uids(),
+                            &now,
+                        );
+                        events_sender
+                            .send(Ok(PinnedEvents(pinned_events)))
+                            .expect("Channel should be sendable");
+                        let mut newest_revisions: HashMap<String, Event> = HashMap::new();
+                        for event in &events {
+                            let is_newest = newest_revisions
+                                .get(&event.uid)
+                                .map_or(true, |newest| is_newer_revision(event, newest));
+                            if is_newest {
+                                newest_revisions.insert(event.uid.clone(), event.clone());
+                            }
+                        }
+                        let today_events =
+                            meeters_ical::events_in_range(&events, today_start, today_end);
                         println!(
                             "There are {} events for today: {:?}",
                             today_events.len(),
                             today_events
                         );
+                        // Notify once per UID about events of today that changed since the last download
+                        let mut updated_uids = HashSet::new();
+                        for event in &today_events {
+                            let is_updated = known_revisions
+                                .get(&event.uid)
+                                .map_or(false, |known| is_newer_revision(event, known));
+                            if is_updated && updated_uids.insert(event.uid.clone()) {
+                                events_sender
+                                    .send(Ok(EventUpdatedNotification(event.clone())))
+                                    .expect("Channel should be sendable");
+                            }
+                        }
+                        known_revisions = newest_revisions;
                         last_events = today_events.clone();
+                        has_today_events = true;
+                        *agenda_events.lock().unwrap() = today_events.clone();
+                        let menu_events = if menu_day == MenuDay::Today {
+                            today_events
+                        } else {
+                            meeters_ical::events_in_range(&events, menu_start, menu_end)
+                        };
                         events_sender
-                            .send(Ok(TodayEvents(today_events)))
+                            .send(Ok(TodayEvents(menu_day, menu_events)))
                             .expect("Channel should be sendable");
                     }
                     Err(e) => {
@@ -417,28 +2076,128 @@ fn main() -> std::io::Result<()> {
                         events_sender
                             .send(Err(()))
                             .expect("Channel should be sendable");
-                        eprintln!("Error getting events: {:?}", e.msg);
+                        eprintln!("Error getting events: {}", e);
                     }
                 }
             }
             // Phase two of the background loop: check whether we have events that are close to occurring and trigger a notification
-            // find the first event that is about to start in the next minute and if we did not notify before, send a notification
+            // find all events that are about to start within one of their lead times and that we did not notify about before
             let now = Local::now();
-            let potential_next_immediate_upcoming_event = last_events.iter().find(|event| {
-                let time_distance_from_now = event.start_timestamp.signed_duration_since(now);
-                time_distance_from_now.num_seconds() > 0
-                    && time_distance_from_now.num_seconds() <= config_event_warning_time_seconds
-            });
-            if let Some(next_immediate_upcoming_event) = potential_next_immediate_upcoming_event {
-                if last_notification_start_time.is_none()
-                    || next_immediate_upcoming_event.start_timestamp
-                        != last_notification_start_time.unwrap()
+            let due_events = due_notifications(
+                &last_events,
+                &now,
+                config_event_warning_time_seconds,
+                config_notify_at_alarms,
+                config_min_notify_duration_minutes,
+                |event, lead_seconds| {
+                    notified_events.contains(&notification_key(event, lead_seconds))
+                        || dismissed_events
+                            .lock()
+                            .unwrap()
+                            .contains(&dismissal_key(event))
+                },
+            );
+            for (upcoming_event, lead_seconds) in due_events {
+                events_sender
+                    .send(Ok(EventNotification(upcoming_event.clone())))
+                    .expect("Channel should be sendable");
+                notified_events.add(
+                    notification_key(upcoming_event, lead_seconds),
+                    upcoming_event,
+                );
+            }
+            // A notification that was neither used to join nor dismissed may have timed out
+            // unnoticed, so we optionally show it once more when the meeting starts
+            if config_renotify_at_start {
+                let potential_started_event = last_events.iter().find(|event| {
+                    let seconds_since_start = now
+                        .signed_duration_since(event.start_timestamp)
+                        .num_seconds();
+                    (0..=RENOTIFY_GRACE_SECONDS).contains(&seconds_since_start)
+                        && notification_lead_times(
+                            event,
+                            config_event_warning_time_seconds,
+                            config_notify_at_alarms,
+                        )
+                        .iter()
+                        .any(|lead_seconds| {
+                            notified_events.contains(&notification_key(event, *lead_seconds))
+                        })
+                        && !dismissed_events
+                            .lock()
+                            .unwrap()
+                            .contains(&dismissal_key(event))
+                });
+                if let Some(started_event) = potential_started_event {
+                    let key = notification_key(started_event, 0);
+                    if !notified_events.contains(&key) {
+                        events_sender
+                            .send(Ok(EventNotification(started_event.clone())))
+                            .expect("Channel should be sendable");
+                        notified_events.add(key, started_event);
+                    }
+                }
+            }
+            // Optionally join the next meeting automatically, this only happens once per meeting
+            if let Some(autojoin_seconds) = config_autojoin_seconds {
+                let potential_autojoin_event = last_events.iter().find(|event| {
+                    let seconds_until_start = event
+                        .start_timestamp
+                        .signed_duration_since(now)
+                        .num_seconds();
+                    event.meeturl.is_some()
+                        && seconds_until_start <= autojoin_seconds
+                        && seconds_until_start >= -AUTOJOIN_GRACE_SECONDS
+                        && !dismissed_events
+                            .lock()
+                            .unwrap()
+                            .contains(&dismissal_key(event))
+                });
+                if let Some(autojoin_event) = potential_autojoin_event {
+                    if last_autojoin_start_time != Some(autojoin_event.start_timestamp) {
+                        events_sender
+                            .send(Ok(AutoJoin(autojoin_event.clone())))
+                            .expect("Channel should be sendable");
+                        // the meeting is open now, so we do not remind about it anymore
+                        dismissed_events
+                            .lock()
+                            .unwrap()
+                            .add(dismissal_key(autojoin_event), autojoin_event);
+                        last_autojoin_start_time = Some(autojoin_event.start_timestamp);
+                    }
+                }
+            }
+            // Once a day we give an overview of the meetings of the day
+            if let Some(morning_summary_hour) = config_morning_summary_hour {
+                let today = now.naive_local().date();
+                if has_today_events
+                    && now.hour() == morning_summary_hour
+                    && last_morning_summary_date != Some(today)
                 {
                     events_sender
-                        .send(Ok(EventNotification(next_immediate_upcoming_event.clone())))
+                        .send(Ok(MorningSummary(format_morning_summary(
+                            &last_events,
+                            today,
+                        ))))
                         .expect("Channel should be sendable");
-                    last_notification_start_time =
-                        Some(next_immediate_upcoming_event.start_timestamp);
+                    last_morning_summary_date = Some(today);
+                }
+            }
+            // Phase three: notify about the end of a meeting when we have just passed its end time
+            if config_notify_meeting_end {
+                let potential_just_ended_event = last_events.iter().find(|event| {
+                    let time_since_end = now.signed_duration_since(event.end_timestamp);
+                    !event.all_day
+                        && time_since_end.num_seconds() >= 0
+                        && time_since_end.num_seconds() <= EVENT_END_NOTIFICATION_GRACE_SECONDS
+                });
+                if let Some(just_ended_event) = potential_just_ended_event {
+                    if last_notification_end_time != Some(just_ended_event.end_timestamp) {
+                        events_sender
+                            .send(Ok(EventEndNotification(just_ended_event.clone())))
+                            .expect("Channel should be sendable");
+                        last_notification_end_time = Some(just_ended_event.end_timestamp);
+                    }
                 }
             }
             thread::sleep(std::time::Duration::from_secs(5));
@@ -448,3 +2207,651 @@ fn main() -> std::io::Result<()> {
     gtk::main();
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::Europe::Berlin;
+
+    fn test_event(summary: &str, start: DateTime<Tz>, end: DateTime<Tz>) -> Event {
+        Event {
+            uid: summary.to_string(),
+            summary: summary.to_string(),
+            description: "".to_string(),
+            location: "".to_string(),
+            meeturl: None,
+            meeting_provider: None,
+            dialin: None,
+            passcode: None,
+            comments: vec![],
+            attachments: vec![],
+            nof_inline_attachments: 0,
+            busy: true,
+            tentative: false,
+            priority: 0,
+            sequence: 0,
+            last_modified: None,
+            num_participants: 0,
+            is_organizer: false,
+            related_to: vec![],
+            alarm_lead_seconds: vec![],
+            calendar_name: None,
+            todo: false,
+            all_day: false,
+            start_timestamp: start,
+            end_timestamp: end,
+        }
+    }
+
+    fn summaries_for_day(events: &[Event], day: u32) -> Vec<String> {
+        meeters_ical::events_in_range(
+            events,
+            Berlin.ymd(2022, 3, day).and_hms(0, 0, 0),
+            Berlin.ymd(2022, 3, day + 1).and_hms(0, 0, 0),
+        )
+        .into_iter()
+        .map(|event| event.summary)
+        .collect()
+    }
+
+    #[test]
+    fn event_spanning_midnight_is_on_both_days() {
+        let events = vec![test_event(
+            "late",
+            Berlin.ymd(2022, 3, 10).and_hms(23, 0, 0),
+            Berlin.ymd(2022, 3, 11).and_hms(1, 0, 0),
+        )];
+        assert_eq!(vec!["late"], summaries_for_day(&events, 10));
+        assert_eq!(vec!["late"], summaries_for_day(&events, 11));
+        assert!(summaries_for_day(&events, 9).is_empty());
+        assert!(summaries_for_day(&events, 12).is_empty());
+    }
+
+    #[test]
+    fn events_touching_midnight_are_only_on_their_own_day() {
+        let events = vec![
+            test_event(
+                "until midnight",
+                Berlin.ymd(2022, 3, 10).and_hms(23, 0, 0),
+                Berlin.ymd(2022, 3, 11).and_hms(0, 0, 0),
+            ),
+            test_event(
+                "from midnight",
+                Berlin.ymd(2022, 3, 11).and_hms(0, 0, 0),
+                Berlin.ymd(2022, 3, 11).and_hms(1, 0, 0),
+            ),
+            test_event(
+                "all day",
+                Berlin.ymd(2022, 3, 11).and_hms(0, 0, 0),
+                Berlin.ymd(2022, 3, 12).and_hms(0, 0, 0),
+            ),
+        ];
+        assert_eq!(vec!["until midnight"], summaries_for_day(&events, 10));
+        assert_eq!(
+            vec!["from midnight", "all day"],
+            summaries_for_day(&events, 11)
+        );
+        assert!(summaries_for_day(&events, 12).is_empty());
+    }
+
+    #[test]
+    fn range_spanning_multiple_days() {
+        let events = vec![
+            test_event(
+                "before",
+                Berlin.ymd(2022, 3, 9).and_hms(9, 0, 0),
+                Berlin.ymd(2022, 3, 9).and_hms(10, 0, 0),
+            ),
+            test_event(
+                "friday",
+                Berlin.ymd(2022, 3, 11).and_hms(9, 0, 0),
+                Berlin.ymd(2022, 3, 11).and_hms(10, 0, 0),
+            ),
+            test_event(
+                "thursday",
+                Berlin.ymd(2022, 3, 10).and_hms(9, 0, 0),
+                Berlin.ymd(2022, 3, 10).and_hms(10, 0, 0),
+            ),
+            test_event(
+                "after",
+                Berlin.ymd(2022, 3, 13).and_hms(9, 0, 0),
+                Berlin.ymd(2022, 3, 13).and_hms(10, 0, 0),
+            ),
+        ];
+        let summaries: Vec<String> = meeters_ical::events_in_range(
+            &events,
+            Berlin.ymd(2022, 3, 10).and_hms(0, 0, 0),
+            Berlin.ymd(2022, 3, 13).and_hms(0, 0, 0),
+        )
+        .into_iter()
+        .map(|event| event.summary)
+        .collect();
+        assert_eq!(vec!["thursday", "friday"], summaries);
+    }
+
+    #[test]
+    fn adjacent_meetings_do_not_overlap() {
+        let first = test_event(
+            "first",
+            Berlin.ymd(2022, 3, 10).and_hms(10, 0, 0),
+            Berlin.ymd(2022, 3, 10).and_hms(11, 0, 0),
+        );
+        let second = test_event(
+            "second",
+            Berlin.ymd(2022, 3, 10).and_hms(11, 0, 0),
+            Berlin.ymd(2022, 3, 10).and_hms(12, 0, 0),
+        );
+        let boundary = Berlin
+            .ymd(2022, 3, 10)
+            .and_hms(11, 0, 0)
+            .with_timezone(&Local);
+        assert!(is_past_event(&first, &boundary));
+        assert!(!is_running_event(&first, &boundary));
+        assert!(!is_past_event(&second, &boundary));
+        assert!(is_running_event(&second, &boundary));
+        let events = vec![first, second];
+        let in_range = |hour| -> Vec<String> {
+            meeters_ical::events_in_range(
+                &events,
+                Berlin.ymd(2022, 3, 10).and_hms(hour, 0, 0),
+                Berlin.ymd(2022, 3, 10).and_hms(hour + 1, 0, 0),
+            )
+            .into_iter()
+            .map(|event| event.summary)
+            .collect()
+        };
+        assert_eq!(vec!["first"], in_range(10));
+        assert_eq!(vec!["second"], in_range(11));
+    }
+
+    #[test]
+    fn short_events_are_not_notified() {
+        let start = Berlin.ymd(2022, 3, 10).and_hms(9, 0, 0);
+        let reminder = test_event("reminder", start, start + chrono::Duration::minutes(2));
+        assert!(!is_long_enough_to_notify(&reminder, 5));
+        assert!(is_long_enough_to_notify(&reminder, 2));
+        assert!(is_long_enough_to_notify(&reminder, 0));
+    }
+
+    #[test]
+    fn notified_events_do_not_hold_back_later_alarms() {
+        let now = Local.ymd(2022, 3, 10).and_hms(9, 55, 0);
+        let start = |minute| {
+            Local
+                .ymd(2022, 3, 10)
+                .and_hms(10, minute, 0)
+                .with_timezone(&Berlin)
+        };
+        let mut first = test_event("first", start(0), start(30));
+        first.alarm_lead_seconds = vec![15 * 60];
+        let mut second = test_event("second", start(5), start(30));
+        second.alarm_lead_seconds = vec![10 * 60];
+        let events = vec![first, second];
+        let summaries = |notified: &[(&str, i64)]| {
+            due_notifications(&events, &now, 60, true, 0, |event, lead_seconds| {
+                notified
+                    .iter()
+                    .any(|(summary, lead)| *summary == event.summary && *lead == lead_seconds)
+            })
+            .into_iter()
+            .map(|(event, lead_seconds)| (event.summary.clone(), lead_seconds))
+            .collect::<Vec<(String, i64)>>()
+        };
+        assert_eq!(
+            vec![("first".to_string(), 900), ("second".to_string(), 600)],
+            summaries(&[])
+        );
+        assert_eq!(
+            vec![("second".to_string(), 600)],
+            summaries(&[("first", 900)])
+        );
+    }
+
+    #[test]
+    fn alarm_lead_times_are_added_to_the_warning_time() {
+        let start = Berlin.ymd(2022, 3, 10).and_hms(9, 0, 0);
+        let mut event = test_event("review", start, start + chrono::Duration::hours(1));
+        event.alarm_lead_seconds = vec![24 * 3600, 600, 60];
+        assert_eq!(vec![60], notification_lead_times(&event, 60, false));
+        assert_eq!(
+            vec![60, 600, 24 * 3600],
+            notification_lead_times(&event, 60, true)
+        );
+    }
+
+    #[test]
+    fn notification_urgency_depends_on_the_event() {
+        let start = Berlin.ymd(2022, 3, 10).and_hms(9, 0, 0);
+        let mut event = test_event("meeting", start, start + chrono::Duration::hours(1));
+        assert_eq!(
+            Urgency::Normal,
+            notification_urgency(&event, Urgency::Normal)
+        );
+        event.priority = 1;
+        assert_eq!(
+            Urgency::Critical,
+            notification_urgency(&event, Urgency::Normal)
+        );
+        event.priority = 0;
+        event.is_organizer = true;
+        assert_eq!(
+            Urgency::Critical,
+            notification_urgency(&event, Urgency::Normal)
+        );
+        event.tentative = true;
+        assert_eq!(Urgency::Low, notification_urgency(&event, Urgency::Normal));
+        event.tentative = false;
+        event.busy = false;
+        assert_eq!(
+            Urgency::Low,
+            notification_urgency(&event, Urgency::Critical)
+        );
+    }
+
+    #[test]
+    fn holidays_are_free_time() {
+        let mut holiday = test_event(
+            "Karfreitag",
+            Berlin.ymd(2022, 4, 15).and_hms(0, 0, 0),
+            Berlin.ymd(2022, 4, 16).and_hms(0, 0, 0),
+        );
+        holiday.all_day = true;
+        let half_day_holiday = test_event(
+            "Half day off",
+            Berlin.ymd(2022, 4, 15).and_hms(12, 0, 0),
+            Berlin.ymd(2022, 4, 15).and_hms(18, 0, 0),
+        );
+        let other_holiday = test_event(
+            "Ostermontag",
+            Berlin.ymd(2022, 4, 18).and_hms(0, 0, 0),
+            Berlin.ymd(2022, 4, 19).and_hms(0, 0, 0),
+        );
+        let holidays = holidays_in_range(
+            &[holiday, half_day_holiday, other_holiday],
+            Berlin.ymd(2022, 4, 15).and_hms(0, 0, 0),
+            Berlin.ymd(2022, 4, 16).and_hms(0, 0, 0),
+        );
+        assert_eq!(2, holidays.len());
+        assert!(holidays.iter().all(|holiday| !holiday.busy));
+        // a timed holiday is still shown with its time
+        assert_eq!(
+            vec![true, false],
+            holidays
+                .iter()
+                .map(|holiday| holiday.all_day)
+                .collect::<Vec<bool>>()
+        );
+    }
+
+    #[test]
+    fn morning_summary_counts_only_todays_meetings() {
+        let today = NaiveDate::from_ymd(2022, 3, 1);
+        let mut video_meeting = test_event(
+            "Standup",
+            Berlin.ymd(2022, 3, 1).and_hms(11, 0, 0),
+            Berlin.ymd(2022, 3, 1).and_hms(11, 15, 0),
+        );
+        video_meeting.meeturl = Some("https://zoom.us/j/1".to_string());
+        let mut free_event = test_event(
+            "Focus",
+            Berlin.ymd(2022, 3, 1).and_hms(8, 0, 0),
+            Berlin.ymd(2022, 3, 1).and_hms(9, 0, 0),
+        );
+        free_event.busy = false;
+        let mut vacation = test_event(
+            "Vacation",
+            Berlin.ymd(2022, 3, 1).and_hms(0, 0, 0),
+            Berlin.ymd(2022, 3, 2).and_hms(0, 0, 0),
+        );
+        vacation.all_day = true;
+        let events = vec![
+            free_event,
+            vacation,
+            test_event(
+                "Review",
+                Berlin.ymd(2022, 3, 1).and_hms(9, 30, 0),
+                Berlin.ymd(2022, 3, 1).and_hms(10, 0, 0),
+            ),
+            video_meeting,
+            test_event(
+                "Tomorrow",
+                Berlin.ymd(2022, 3, 2).and_hms(9, 0, 0),
+                Berlin.ymd(2022, 3, 2).and_hms(10, 0, 0),
+            ),
+        ];
+        assert_eq!(
+            "You have 2 meetings today, first at 09:30 (1 with a video link)",
+            format_morning_summary(&events, today)
+        );
+        assert_eq!(
+            "You have 1 meeting today at 09:00",
+            format_morning_summary(&events, NaiveDate::from_ymd(2022, 3, 2))
+        );
+        assert_eq!(
+            "You have no meetings today",
+            format_morning_summary(&events, NaiveDate::from_ymd(2022, 3, 3))
+        );
+    }
+
+    #[test]
+    fn zero_duration_events_are_not_all_day() {
+        let reminder = test_event(
+            "Reminder",
+            Berlin.ymd(2022, 3, 1).and_hms(9, 0, 0),
+            Berlin.ymd(2022, 3, 1).and_hms(9, 0, 0),
+        );
+        assert_eq!("09:00", format_event_time(&reminder, false));
+        assert_eq!("09:00 Reminder", format_agenda(&[reminder]));
+        let mut all_day_event = test_event(
+            "Vacation",
+            Berlin.ymd(2022, 3, 1).and_hms(0, 0, 0),
+            Berlin.ymd(2022, 3, 2).and_hms(0, 0, 0),
+        );
+        all_day_event.all_day = true;
+        assert_eq!("All Day", format_event_time(&all_day_event, false));
+        assert_eq!("All Day Vacation", format_agenda(&[all_day_event]));
+    }
+
+    #[test]
+    fn quoted_printable_calendars_are_decoded() {
+        let body = "BEGIN:VCALENDAR=0D=0AVERSION:2.0=0D=0ABEGIN:VEVENT=0D=0ASUMMARY:Caf=C3=A9 with a very long title that had to be wr=\r\napped=0D=0AEND:VEVENT=0D=0AEND:VCALENDAR=0D=0A";
+        assert_eq!(
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VEVENT\r\nSUMMARY:Café with a very long title that had to be wrapped\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n",
+            decode_transfer_encoding(Some("quoted-printable"), body.to_string()).unwrap()
+        );
+    }
+
+    #[test]
+    fn base64_calendars_are_decoded() {
+        assert_eq!(
+            "BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n",
+            decode_transfer_encoding(
+                Some("base64"),
+                "QkVHSU46VkNBTEVOREFSDQpFTkQ6\r\nVkNBTEVOREFSDQo=".to_string()
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            "BEGIN:VCALENDAR",
+            decode_transfer_encoding(None, "BEGIN:VCALENDAR".to_string()).unwrap()
+        );
+    }
+
+    /// Serves the given pages, each with its extra headers, to one request each
+    fn serve_calendar_pages(pages: Vec<(&'static str, String)>) -> String {
+        use std::io::{BufRead, BufReader, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            for (headers, body) in pages {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                    line.clear();
+                }
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/calendar\r\nContent-Length: {}\r\nConnection: close\r\n{}\r\n{}",
+                    body.len(),
+                    headers,
+                    body
+                )
+                .unwrap();
+            }
+        });
+        format!("http://127.0.0.1:{}/calendar.ics", port)
+    }
+
+    #[test]
+    fn paginated_calendars_are_merged() {
+        let page = |uid: &str, day: u32| {
+            format!(
+                "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//meeters//test//EN\r\nBEGIN:VEVENT\r\nUID:{0}\r\nSUMMARY:{0}\r\nDTSTART:202203{1:02}T090000Z\r\nDTEND:202203{1:02}T100000Z\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n",
+                uid, day
+            )
+        };
+        let url = serve_calendar_pages(vec![
+            (
+                "Link: </calendar.ics?page=2>; rel=\"next\", </calendar.ics>; rel=\"first\"\r\n",
+                page("first", 1),
+            ),
+            ("", page("second", 2)),
+        ]);
+        let agent = ureq::AgentBuilder::new().build();
+        let text = get_ical(&agent, &url, &None, "meeters-test").unwrap();
+        assert_eq!(1, text.matches("BEGIN:VCALENDAR").count());
+        let events = meeters_ical::extract_events(
+            &text,
+            &chrono_tz::UTC,
+            &meeters_ical::ExtractOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            vec!["first", "second"],
+            events
+                .iter()
+                .map(|event| event.summary.as_str())
+                .collect::<Vec<&str>>()
+        );
+    }
+
+    #[test]
+    fn next_page_is_found_in_the_link_header() {
+        let page_url = "https://example.com/feeds/team.ics?page=1";
+        assert_eq!(
+            Some("https://example.com/feeds/team.ics?page=2".to_string()),
+            next_page_url(page_url, "<team.ics?page=2>; rel=\"next\"")
+        );
+        assert_eq!(
+            Some("https://other.example.com/3".to_string()),
+            next_page_url(
+                page_url,
+                "<https://example.com/feeds/team.ics>; rel=first, <https://other.example.com/3>; rel=\"last next\""
+            )
+        );
+        assert_eq!(
+            None,
+            next_page_url(page_url, "<team.ics?page=0>; rel=\"prev\"")
+        );
+    }
+
+    #[test]
+    fn secondary_time_uses_the_offset_of_the_day_of_the_event() {
+        let new_york: Tz = "America/New_York".parse().unwrap();
+        // the US switch to daylight saving time two weeks before Europe does
+        let before_us_dst = test_event(
+            "Sync",
+            Berlin.ymd(2022, 3, 10).and_hms(15, 0, 0),
+            Berlin.ymd(2022, 3, 10).and_hms(16, 0, 0),
+        );
+        assert_eq!(
+            "15:00 - 16:00 / 09:00 EST",
+            with_secondary_time(
+                format_event_time(&before_us_dst, false),
+                &before_us_dst,
+                Some(new_york)
+            )
+        );
+        let after_us_dst = test_event(
+            "Sync",
+            Berlin.ymd(2022, 3, 20).and_hms(15, 0, 0),
+            Berlin.ymd(2022, 3, 20).and_hms(16, 0, 0),
+        );
+        assert_eq!(
+            "15:00 - 16:00 / 10:00 EDT",
+            with_secondary_time(
+                format_event_time(&after_us_dst, false),
+                &after_us_dst,
+                Some(new_york)
+            )
+        );
+        assert_eq!(
+            "15:00 - 16:00",
+            with_secondary_time(format_event_time(&after_us_dst, false), &after_us_dst, None)
+        );
+    }
+
+    #[test]
+    fn meeting_url_is_substituted_or_appended_in_open_commands() {
+        assert_eq!(
+            vec![
+                "firefox",
+                "--new-window",
+                "https://meet.google.com/abc-defg-hij"
+            ],
+            command_from_template(
+                "firefox --new-window {url}",
+                "https://meet.google.com/abc-defg-hij"
+            )
+        );
+        assert_eq!(
+            vec!["zoom", "--url=https://zoom.us/j/1"],
+            command_from_template("zoom --url={url}", "https://zoom.us/j/1")
+        );
+        assert_eq!(
+            vec!["xdg-open", "https://zoom.us/j/1"],
+            command_from_template("xdg-open", "https://zoom.us/j/1")
+        );
+    }
+
+    #[test]
+    fn configured_polling_interval_wins_over_the_calendar_refresh_interval() {
+        let hour = Some(chrono::Duration::hours(1));
+        assert_eq!(5000, polling_interval_ms(Some(5000), hour));
+        assert_eq!(3_600_000, polling_interval_ms(None, hour));
+        assert_eq!(
+            MIN_CALENDAR_REFRESH_INTERVAL_MS,
+            polling_interval_ms(None, Some(chrono::Duration::seconds(1)))
+        );
+        assert_eq!(DEFAULT_POLLING_INTERVAL_MS, polling_interval_ms(None, None));
+    }
+
+    #[test]
+    fn poll_minutes_are_parsed_from_lists_steps_and_cron_expressions() {
+        assert_eq!(Ok(vec![0, 30]), parse_poll_minutes("30,0"));
+        assert_eq!(Ok(vec![0, 20, 40]), parse_poll_minutes("*/20"));
+        assert_eq!(Ok(vec![0, 30]), parse_poll_minutes("0,30 * * * *"));
+        assert_eq!(60, parse_poll_minutes("*").unwrap().len());
+        assert!(parse_poll_minutes("").is_err());
+        assert!(parse_poll_minutes("60").is_err());
+        assert!(parse_poll_minutes("*/0").is_err());
+        assert!(parse_poll_minutes("0 9 * * *").is_err());
+    }
+
+    #[test]
+    fn next_poll_time_is_the_next_scheduled_minute() {
+        let poll_minutes = vec![0, 30];
+        let at = |hour, minute, second| Local.ymd(2022, 3, 1).and_hms(hour, minute, second);
+        assert_eq!(at(9, 30, 0), next_poll_time(&at(9, 12, 45), &poll_minutes));
+        assert_eq!(at(10, 0, 0), next_poll_time(&at(9, 30, 0), &poll_minutes));
+        assert_eq!(at(10, 0, 0), next_poll_time(&at(9, 59, 59), &poll_minutes));
+    }
+
+    #[test]
+    fn past_events_can_be_hidden_from_the_menu() {
+        let now = Local::now();
+        let local_now = now.with_timezone(&Berlin);
+        let events = vec![
+            test_event(
+                "Over",
+                local_now - chrono::Duration::hours(2),
+                local_now - chrono::Duration::hours(1),
+            ),
+            test_event(
+                "Running",
+                local_now - chrono::Duration::minutes(30),
+                local_now + chrono::Duration::minutes(30),
+            ),
+            test_event(
+                "Upcoming",
+                local_now + chrono::Duration::hours(1),
+                local_now + chrono::Duration::hours(2),
+            ),
+        ];
+        let mut menu_config = MenuConfig {
+            sort: MenuSort::Chronological,
+            compact: false,
+            show_duration: false,
+            show_participants: false,
+            max_label_len: None,
+            secondary_tz: None,
+            hide_past: false,
+            show_calendar_name: false,
+        };
+        let summaries = |menu_config| {
+            get_menu_events(&events, menu_config, &now)
+                .iter()
+                .map(|event| event.summary.clone())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(vec!["Over", "Running", "Upcoming"], summaries(menu_config));
+        menu_config.hide_past = true;
+        assert_eq!(vec!["Running", "Upcoming"], summaries(menu_config));
+    }
+
+    #[test]
+    fn next_occurrence_of_pinned_meetings() {
+        let now = Local::now();
+        let start = now.with_timezone(&Berlin);
+        let events = vec![
+            test_event(
+                "standup",
+                start - chrono::Duration::days(1),
+                start - chrono::Duration::days(1) + chrono::Duration::minutes(15),
+            ),
+            test_event(
+                "standup",
+                start + chrono::Duration::days(2),
+                start + chrono::Duration::days(2) + chrono::Duration::minutes(15),
+            ),
+            test_event(
+                "standup",
+                start + chrono::Duration::days(1),
+                start + chrono::Duration::days(1) + chrono::Duration::minutes(15),
+            ),
+            test_event(
+                "retro",
+                start + chrono::Duration::hours(1),
+                start + chrono::Duration::hours(2),
+            ),
+            test_event(
+                "planning",
+                start - chrono::Duration::hours(2),
+                start - chrono::Duration::hours(1),
+            ),
+        ];
+        let pinned_uids: HashSet<String> = vec!["standup", "planning", "retro"]
+            .into_iter()
+            .map(|uid| uid.to_string())
+            .collect();
+        let pinned_events = get_next_pinned_occurrences(&events, &pinned_uids, &now);
+        assert_eq!(
+            vec![
+                ("retro".to_string(), start + chrono::Duration::hours(1)),
+                ("standup".to_string(), start + chrono::Duration::days(1))
+            ],
+            pinned_events
+                .into_iter()
+                .map(|event| (event.uid, event.start_timestamp))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn newer_sequence_takes_precedence_over_last_modified() {
+        let start = Berlin.ymd(2022, 3, 10).and_hms(9, 0, 0);
+        let mut previous = test_event("standup", start, start + chrono::Duration::minutes(15));
+        previous.sequence = 1;
+        previous.last_modified = Some(start);
+        let mut event = previous.clone();
+        assert!(!is_newer_revision(&event, &previous));
+        event.last_modified = Some(start + chrono::Duration::hours(1));
+        assert!(is_newer_revision(&event, &previous));
+        event.sequence = 0;
+        assert!(!is_newer_revision(&event, &previous));
+        event.sequence = 2;
+        event.last_modified = None;
+        assert!(is_newer_revision(&event, &previous));
+    }
+}
@@ -1,25 +1,108 @@
 use crate::custom_timezone::CustomTz;
 use crate::ical_util::unescape_string;
+use crate::meeting_url::resolve_meeting_url;
+use crate::text_util::truncate_with_ellipsis;
 use crate::timezones::parse_ical_timezones;
 use crate::timezones::parse_tzid;
+use crate::timezones::resolve_local_datetime;
 use chrono::prelude::*;
 use chrono::Duration;
 use chrono_tz::{Tz, UTC};
-use either::{Either, Left};
-use ical::parser::ical::component::{IcalCalendar, IcalEvent};
+use either::{Either, Left, Right};
+use ical::parser::ical::component::{IcalAlarm, IcalCalendar, IcalEvent, IcalTodo};
 use ical::property::Property;
 use lazy_static::lazy_static;
 use regex::Regex;
 use rrule::RRuleSet;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
 
 use crate::domain::*;
 use crate::ical_util::{
-    find_param, find_property, find_property_value, is_ical_date, properties_to_string,
+    find_localized_property_value, find_param, find_property, find_property_value,
+    ical_event_to_string, is_ical_date, properties_to_string,
 };
 use multimap::MultiMap;
 
+/// Options that influence which events are extracted from a calendar and how
+#[derive(Clone, Default)]
+pub struct ExtractOptions {
+    /// The maximum number of events to process, this protects us against huge calendar feeds
+    pub max_events: Option<usize>,
+    /// When an event has SUMMARY or DESCRIPTION properties in multiple languages we prefer the
+    /// one whose LANGUAGE parameter matches this language
+    pub preferred_language: Option<String>,
+    /// The email address of the user, used to determine which events the user organizes
+    pub user_email: Option<String>,
+    /// Events whose summary matches one of these patterns are dropped completely
+    pub hide_summary_patterns: Vec<Regex>,
+    /// Whether to also extract to-dos with a due date as all-day events
+    pub include_todos: bool,
+    /// What to do with timestamps whose TZID can not be resolved
+    pub unknown_tz_policy: UnknownTzPolicy,
+    /// Whether to log the transitions of the timezones defined in the calendar
+    pub debug_timezones: bool,
+    /// Descriptions are cut off after this many characters, some invitations carry whole email
+    /// threads that we would otherwise keep in memory for every occurrence
+    pub max_description_len: Option<usize>,
+}
+
+/// How timestamps are interpreted when their TZID is neither a known timezone nor defined in a
+/// VTIMEZONE of the calendar
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnknownTzPolicy {
+    /// Interpret the timestamp in the local timezone
+    Local,
+    /// Interpret the timestamp as UTC
+    Utc,
+    /// Skip the event with a warning
+    Error,
+}
+
+impl Default for UnknownTzPolicy {
+    fn default() -> Self {
+        UnknownTzPolicy::Local
+    }
+}
+
+/// Resolves a TZID to a timezone, applying the policy when we do not know the timezone
+fn resolve_tzid<'a>(
+    tzid: &str,
+    calendar_timezones: &'a HashMap<String, CustomTz>,
+    local_tz: &Tz,
+    unknown_tz_policy: UnknownTzPolicy,
+) -> Result<Either<Tz, &'a CustomTz>, CalendarError> {
+    match parse_tzid(tzid, calendar_timezones) {
+        Ok(timezone) => Ok(timezone),
+        Err(e) => match unknown_tz_policy {
+            UnknownTzPolicy::Local => Ok(Left(*local_tz)),
+            UnknownTzPolicy::Utc => Ok(Left(UTC)),
+            UnknownTzPolicy::Error => Err(CalendarError::Timezone {
+                msg: format!("error in timezone string parsing: {}", e),
+            }),
+        },
+    }
+}
+
+/// Returns the first TZID of the event's timestamps that can not be resolved, if any
+fn find_unknown_tzid(
+    ical_event: &IcalEvent,
+    calendar_timezones: &HashMap<String, CustomTz>,
+) -> Option<String> {
+    ["DTSTART", "DTEND", "RECURRENCE-ID"]
+        .iter()
+        .filter_map(|name| find_property(&ical_event.properties, name))
+        .filter_map(|prop| {
+            prop.params
+                .as_ref()
+                .and_then(|params| find_param(params, "TZID"))
+        })
+        .map(|values| unescape_string(&values[0]))
+        .find(|tzid| parse_tzid(tzid, calendar_timezones).is_err())
+}
+
 /// Parses datetimes of the format 'YYYYMMDDTHHMMSS'
 ///
 /// See <https://tools.ietf.org/html/rfc5545#section-3.3.5>
@@ -28,30 +111,44 @@ fn parse_ical_datetime<T: TimeZone>(
     tz: &Either<Tz, &CustomTz>,
     target_tz: &T,
 ) -> Result<DateTime<T>, CalendarError> {
-    match NaiveDateTime::parse_from_str(datetime, "%Y%m%dT%H%M%S") {
+    // Some feeds add fractional seconds (e.g. 20201102T235401.000), we accept but ignore them
+    match NaiveDateTime::parse_from_str(datetime, "%Y%m%dT%H%M%S%.f") {
         Ok(d) => {
+            let d = d.with_nanosecond(0).unwrap();
             if tz.is_left() {
-                Ok(tz
-                    .left()
-                    .unwrap()
-                    .from_local_datetime(&d)
-                    .unwrap()
-                    .with_timezone(target_tz))
+                Ok(resolve_local_datetime(&tz.left().unwrap(), &d).with_timezone(target_tz))
             } else {
-                Ok(tz
-                    .right()
-                    .unwrap()
-                    .from_local_datetime(&d)
-                    .unwrap()
-                    .with_timezone(target_tz))
+                Ok(resolve_local_datetime(tz.right().unwrap(), &d).with_timezone(target_tz))
             }
             // println!(
             //     "Converting timezones between {} and {}, which means {} to {}",
             //     tz, target_tz, datetime, converted
             // );
         }
-        Err(_) => Err(CalendarError {
-            msg: "Can't parse datetime string with tzid".to_string(),
+        Err(_) => Err(CalendarError::Parse {
+            msg: format!(
+                "Can't parse datetime '{}', expected the form YYYYMMDDTHHMMSS",
+                datetime
+            ),
+        }),
+    }
+}
+
+/// RFC 5545 does not allow it, but some feeds embed a UTC offset in the timestamp
+/// (e.g. 20201102T235401+0200). That is unambiguous so we just convert it to the local timezone.
+fn parse_ical_datetime_with_offset(datetime: &str, local_tz: &Tz) -> Option<DateTime<Tz>> {
+    DateTime::parse_from_str(datetime, "%Y%m%dT%H%M%S%.f%z")
+        .ok()
+        .map(|d| d.with_nanosecond(0).unwrap().with_timezone(local_tz))
+}
+
+/// Returns the value of a property or an error when the value is missing or empty, as can happen
+/// with malformed events.
+fn require_value(prop: &Property) -> Result<&String, CalendarError> {
+    match &prop.value {
+        Some(value) if !value.is_empty() => Ok(value),
+        _ => Err(CalendarError::Parse {
+            msg: format!("Property {} has no value", prop.name),
         }),
     }
 }
@@ -66,26 +163,24 @@ fn extract_ical_datetime(
     prop: &Property,
     calendar_timezones: &HashMap<String, CustomTz>,
     local_tz: &Tz,
+    unknown_tz_policy: UnknownTzPolicy,
 ) -> Result<DateTime<Tz>, CalendarError> {
-    let date_time_str = prop.value.as_ref().unwrap();
+    let date_time_str = require_value(prop)?;
     if prop.params.is_some() && find_param(prop.params.as_ref().unwrap(), "TZID").is_some() {
         // timestamp with an explicit timezone: YYYYMMDDTHHMMSS
         // We are assuming there is only one value in the TZID param
         let tzid = unescape_string(&find_param(prop.params.as_ref().unwrap(), "TZID").unwrap()[0]);
         // println!("We have a TZID: {}", tzid);
-        match parse_tzid(&tzid, calendar_timezones) {
-            Ok(timezone) => parse_ical_datetime(date_time_str, &timezone, local_tz),
-            // in case we can't parse the timezone ID we just default to local, also not optimal
-            Err(_) => {
-                // println!("We have an error parsing the source tzid");
-                parse_ical_datetime(date_time_str, &Left(*local_tz), local_tz)
-            }
-        }
+        let timezone = resolve_tzid(&tzid, calendar_timezones, local_tz, unknown_tz_policy)?;
+        parse_ical_datetime(date_time_str, &timezone, local_tz)
     } else {
         // It is either
         //  - a datetime with no timezone: 20201102T235401
         //  - a datetime with in UTC:      20201102T235401Z
-        if date_time_str.ends_with('Z') {
+        //  - a datetime with an offset:   20201102T235401+0200 (not RFC 5545 compliant)
+        if let Some(offset_datetime) = parse_ical_datetime_with_offset(date_time_str, local_tz) {
+            Ok(offset_datetime)
+        } else if date_time_str.ends_with('Z') {
             // println!("We assume UTC because of Z");
             parse_ical_datetime(
                 date_time_str.strip_suffix('Z').unwrap(),
@@ -110,7 +205,7 @@ fn parse_ical_date_notz(date: &str, tz: &Tz) -> Result<DateTime<Tz>, CalendarErr
         // date that represents a particular _day_, not a time. Therefore we need to make sure that
         // we don't accidentally shift it into another day
         Ok(d) => Ok(tz.ymd(d.year(), d.month(), d.day()).and_hms(0, 0, 0)),
-        Err(chrono_err) => Err(CalendarError {
+        Err(chrono_err) => Err(CalendarError::Parse {
             msg: format!(
                 "Can't parse date '{:?}' with cause: {:?}",
                 date,
@@ -121,7 +216,7 @@ fn parse_ical_date_notz(date: &str, tz: &Tz) -> Result<DateTime<Tz>, CalendarErr
 }
 
 fn extract_ical_date(prop: &Property, local_tz: &Tz) -> Result<DateTime<Tz>, CalendarError> {
-    parse_ical_date_notz(prop.value.as_ref().unwrap(), local_tz)
+    parse_ical_date_notz(require_value(prop)?, local_tz)
 }
 
 /// This encapsulates the logic for parsing DTSTART and DTEND ical properties.
@@ -131,11 +226,30 @@ fn extract_start_end_time(
     ical_event: &IcalEvent,
     calendar_timezones: &HashMap<String, CustomTz>,
     local_tz: &Tz,
+    unknown_tz_policy: UnknownTzPolicy,
 ) -> Result<(DateTime<Tz>, DateTime<Tz>, bool), CalendarError> {
     // we assume that DTSTART is mandatory, the spec sort of says that but also mentions something called
     // a "METHOD", ignoring that
-    let start_property = find_property(&ical_event.properties, "DTSTART").unwrap();
+    let uid = find_property_value(&ical_event.properties, "UID").unwrap_or_default();
+    let start_property = match find_property(&ical_event.properties, "DTSTART") {
+        Some(p) => p,
+        None => {
+            return Err(CalendarError::Parse {
+                msg: format!("missing DTSTART for event with UID '{}'", uid),
+            })
+        }
+    };
+    if require_value(start_property).is_err() {
+        return Err(CalendarError::Parse {
+            msg: format!("DTSTART without a value for event with UID '{}'", uid),
+        });
+    }
     let end_property = find_property(&ical_event.properties, "DTEND");
+    if end_property.map_or(false, |p| require_value(p).is_err()) {
+        return Err(CalendarError::Parse {
+            msg: format!("DTEND without a value for event with UID '{}'", uid),
+        });
+    }
     // The start property can be a "date":
     //    in this case it has a param called VALUE with the value DATE
     // The start property can also be a "date-time":
@@ -148,7 +262,7 @@ fn extract_start_end_time(
         // the first real value of the VALUE param should be "DATE"
         let value_param = &find_param(start_property.params.as_ref().unwrap(), "VALUE").unwrap()[0];
         if value_param != "DATE" {
-            return Err(CalendarError{ msg: format!("Encountered DTSTART with a VALUE parameter that has a value different from 'DATE': {}", value_param) });
+            return Err(CalendarError::UnsupportedEvent { msg: format!("Encountered DTSTART with a VALUE parameter that has a value different from 'DATE': {}", value_param) });
         }
         // start property is a "DATE", which indicates a whole day or multi day event
         // see https://tools.ietf.org/html/rfc5545#section-3.6.1 and specifically the discussion on DTSTART
@@ -162,61 +276,383 @@ fn extract_start_end_time(
         // not a whole day event, so real times, there should be an end time
         match end_property {
             Some(p) => {
-                let start_time =
-                    extract_ical_datetime(start_property, calendar_timezones, local_tz)?;
-                let end_time = extract_ical_datetime(p, calendar_timezones, local_tz)?;
+                let start_time = extract_ical_datetime(
+                    start_property,
+                    calendar_timezones,
+                    local_tz,
+                    unknown_tz_policy,
+                )?;
+                let end_time =
+                    extract_ical_datetime(p, calendar_timezones, local_tz, unknown_tz_policy)?;
                 Ok((start_time, end_time, false))
             }
-            None => Err(CalendarError {
+            None => Err(CalendarError::Parse {
                 msg: "missing end time for an event".to_string(),
             }),
         }
     }
 }
 
-fn parse_zoom_url(text: &str) -> Option<String> {
+/// Extracts a phone dial-in from free text such as "Dial +1-555-123-4567, PIN 12345". We only
+/// consider numbers that are introduced by a keyword like "dial" or "phone" to avoid picking up
+/// arbitrary digits. The result is the number as written, followed by " PIN <pin>" when a PIN or
+/// conference ID is present.
+fn parse_dialin(text: &str) -> Option<String> {
     lazy_static! {
-        static ref ZOOM_URL_REGEX: regex::Regex =
-            Regex::new(r"https?://[^\s]*zoom.us/(j|my)/[^\s\n\r<>]+").unwrap();
+        static ref PHONE_REGEX: regex::Regex = Regex::new(
+            r"(?i)\b(?:dial(?:-in)?|phone|tel|call)\b[^+\d]{0,20}(\+?\d[\d\s\-().]{6,}\d)"
+        )
+        .unwrap();
+        static ref PIN_REGEX: regex::Regex = Regex::new(
+            r"(?i)\b(?:pin|conference id|access code|participant code)\b\s*[:#]?\s*(\d[\d ]*\d)"
+        )
+        .unwrap();
+    }
+    let number = PHONE_REGEX
+        .captures(text)?
+        .get(1)?
+        .as_str()
+        .trim()
+        .to_string();
+    match PIN_REGEX.captures(text).and_then(|c| c.get(1)) {
+        Some(pin) => Some(format!("{} PIN {}", number, pin.as_str().replace(' ', ""))),
+        None => Some(number),
+    }
+}
+
+/// Extracts a meeting passcode from free text such as "Meeting ID: 123 456 789 Passcode: 4711ab".
+/// We require at least one digit so that phrases like "password protected" are not mistaken for
+/// a passcode.
+fn parse_passcode(text: &str) -> Option<String> {
+    lazy_static! {
+        static ref PASSCODE_REGEX: regex::Regex = Regex::new(
+            r"(?i)\b(?:passcode|pass code|password|meeting password|kenncode)\b\s*[:#]?\s*([a-z0-9]*\d[a-z0-9]*)\b"
+        )
+        .unwrap();
+    }
+    PASSCODE_REGEX
+        .captures(text)
+        .and_then(|c| c.get(1))
+        .map(|passcode| passcode.as_str().to_string())
+}
+
+/// Returns the URLs of all ATTACH properties and the number of attachments that are inlined as
+/// binary content. We have no use for the inline content itself so it is skipped.
+///
+/// See <https://tools.ietf.org/html/rfc5545#section-3.8.1.1>
+fn parse_attachments(properties: &[Property]) -> (Vec<String>, usize) {
+    let mut attachments = vec![];
+    let mut nof_inline_attachments = 0;
+    for property in properties.iter().filter(|p| p.name == "ATTACH") {
+        let is_binary = property
+            .params
+            .as_ref()
+            .and_then(|params| find_param(params, "VALUE"))
+            .map_or(false, |values| values[0] == "BINARY");
+        if is_binary {
+            nof_inline_attachments += 1;
+        } else if let Some(uri) = &property.value {
+            attachments.push(unescape_string(uri));
+        }
+    }
+    (attachments, nof_inline_attachments)
+}
+
+/// Determines whether the event blocks time. TRANSP is authoritative, but Exchange exports often
+/// leave it out and only provide their own busy status.
+///
+/// See <https://tools.ietf.org/html/rfc5545#section-3.8.2.7>
+fn parse_busy(properties: &[Property]) -> bool {
+    match find_property_value(properties, "TRANSP").as_deref() {
+        Some("TRANSPARENT") => false,
+        Some("OPAQUE") => true,
+        _ => !matches!(
+            find_property_value(properties, "X-MICROSOFT-CDO-BUSYSTATUS").as_deref(),
+            Some("FREE")
+        ),
+    }
+}
+
+/// Whether the event is tentative, either because of its STATUS or the Exchange busy status
+///
+/// See <https://tools.ietf.org/html/rfc5545#section-3.8.1.11>
+fn parse_tentative(properties: &[Property]) -> bool {
+    find_property_value(properties, "STATUS").as_deref() == Some("TENTATIVE")
+        || find_property_value(properties, "X-MICROSOFT-CDO-BUSYSTATUS").as_deref()
+            == Some("TENTATIVE")
+}
+
+/// Whether the event was cancelled. For the master of a recurring event this cancels the whole
+/// series, for a modifying event just that one occurrence.
+///
+/// See <https://tools.ietf.org/html/rfc5545#section-3.8.1.11>
+fn is_cancelled(properties: &[Property]) -> bool {
+    find_property_value(properties, "STATUS").as_deref() == Some("CANCELLED")
+}
+
+/// Whether the ORGANIZER of the event has the given email address. The organizer is a
+/// CAL-ADDRESS which is usually a mailto URI.
+///
+/// See <https://tools.ietf.org/html/rfc5545#section-3.8.4.3>
+fn is_organized_by(properties: &[Property], email: &str) -> bool {
+    find_property_value(properties, "ORGANIZER").map_or(false, |organizer| {
+        let address = match organizer.get(..7) {
+            Some(scheme) if scheme.eq_ignore_ascii_case("mailto:") => &organizer[7..],
+            _ => &organizer[..],
+        };
+        address.trim().eq_ignore_ascii_case(email.trim())
+    })
+}
+
+/// How many seconds before the start of the event its alarms go off. We only support triggers
+/// relative to the start, alarms at an absolute time or relative to the end are ignored.
+///
+/// See <https://tools.ietf.org/html/rfc5545#section-3.8.6.3>
+fn parse_alarm_leads(alarms: &[IcalAlarm]) -> Vec<i64> {
+    let has_param = |trigger: &Property, name: &str, value: &str| {
+        trigger
+            .params
+            .as_ref()
+            .and_then(|params| find_param(params, name))
+            .map_or(false, |values| values.iter().any(|v| v == value))
+    };
+    let mut leads: Vec<i64> = alarms
+        .iter()
+        .filter_map(|alarm| {
+            let trigger = find_property(&alarm.properties, "TRIGGER")?;
+            if has_param(trigger, "RELATED", "END") || has_param(trigger, "VALUE", "DATE-TIME") {
+                return None;
+            }
+            // alarms after the start of the event are of no use for a reminder
+            let before_start = trigger.value.as_ref()?.trim().strip_prefix('-')?;
+            parse_ical_duration(before_start).map(|lead| lead.num_seconds())
+        })
+        .collect();
+    leads.sort_unstable_by(|a, b| b.cmp(a));
+    leads.dedup();
+    leads
+}
+
+/// The display name of the calendar. Most servers still use the X-WR-CALNAME extension, we fall
+/// back to the standardized NAME property.
+///
+/// See <https://tools.ietf.org/html/rfc7986#section-5.1>
+fn parse_calendar_name(calendar: &IcalCalendar) -> Option<String> {
+    find_property_value(&calendar.properties, "X-WR-CALNAME")
+        .or_else(|| find_property_value(&calendar.properties, "NAME"))
+        .map(|name| unescape_string(name.trim()))
+        .filter(|name| !name.is_empty())
+}
+
+/// An iTIP message with METHOD:CANCEL withdraws all the events it contains, they usually do not
+/// carry a STATUS:CANCELLED themselves. We mark them as cancelled so they are dropped like any
+/// other cancelled event. Published feeds use METHOD:PUBLISH or no METHOD at all and are left
+/// alone.
+///
+/// See <https://tools.ietf.org/html/rfc5546#section-3.2.5>
+fn apply_cancel_method(calendar: &mut IcalCalendar) {
+    let is_cancel_method = find_property_value(&calendar.properties, "METHOD")
+        .map_or(false, |method| method.trim().eq_ignore_ascii_case("CANCEL"));
+    if !is_cancel_method {
+        return;
+    }
+    for event in calendar.events.iter_mut() {
+        event.properties.retain(|p| p.name != "STATUS");
+        event.properties.push(Property {
+            name: "STATUS".to_string(),
+            params: None,
+            value: Some("CANCELLED".to_string()),
+        });
     }
-    ZOOM_URL_REGEX
-        .find(text)
-        .map(|mat| mat.as_str().to_string())
 }
 
 // See https://tools.ietf.org/html/rfc5545#section-3.6.1
 fn parse_event(
     ical_event: &IcalEvent,
+    calendar_name: Option<&str>,
     calendar_timezones: &HashMap<String, CustomTz>,
     local_tz: &Tz,
+    options: &ExtractOptions,
 ) -> Result<Event, CalendarError> {
+    let preferred_language = options.preferred_language.as_deref();
+    let uid = find_property_value(&ical_event.properties, "UID").unwrap_or_else(|| "".to_string());
     let summary = unescape_string(
-        &find_property_value(&ical_event.properties, "SUMMARY").unwrap_or_else(|| "".to_string()),
+        &find_localized_property_value(&ical_event.properties, "SUMMARY", preferred_language)
+            .unwrap_or_else(|| "".to_string()),
     );
     let description = unescape_string(
-        &find_property_value(&ical_event.properties, "DESCRIPTION")
+        &find_localized_property_value(&ical_event.properties, "DESCRIPTION", preferred_language)
             .unwrap_or_else(|| "".to_string()),
     );
     let location = unescape_string(
         &find_property_value(&ical_event.properties, "LOCATION").unwrap_or_else(|| "".to_string()),
     );
     // println!("Parsing event '{}'", summary);
-    let (start_timestamp, end_timestamp, all_day) =
-        extract_start_end_time(ical_event, calendar_timezones, local_tz)?; // ? short circuits the error
-    let meeturl = parse_zoom_url(&location)
-        .or_else(|| parse_zoom_url(&summary))
-        .or_else(|| parse_zoom_url(&description));
+    let (start_timestamp, end_timestamp, all_day) = extract_start_end_time(
+        ical_event,
+        calendar_timezones,
+        local_tz,
+        options.unknown_tz_policy,
+    )?; // ? short circuits the error
+    let (meeturl, meeting_provider) =
+        match resolve_meeting_url(&[&location, &summary, &description]) {
+            Some((meeturl, meeting_provider)) => (Some(meeturl), Some(meeting_provider)),
+            None => (None, None),
+        };
+    let comments = ical_event
+        .properties
+        .iter()
+        .filter(|p| p.name == "COMMENT")
+        .filter_map(|p| p.value.as_ref().map(|value| unescape_string(value)))
+        .collect();
+    let dialin = parse_dialin(&description);
+    let passcode = parse_passcode(&description);
+    // only now that we found everything we need in the full text we can cut it short
+    let description = match options.max_description_len {
+        Some(max_description_len) => truncate_with_ellipsis(&description, max_description_len),
+        None => description,
+    };
+    let (attachments, nof_inline_attachments) = parse_attachments(&ical_event.properties);
+    let busy = parse_busy(&ical_event.properties);
+    let tentative = parse_tentative(&ical_event.properties);
+    // See https://tools.ietf.org/html/rfc5545#section-3.8.4.5
+    let related_to = ical_event
+        .properties
+        .iter()
+        .filter(|p| p.name == "RELATED-TO")
+        .filter_map(|p| p.value.as_ref().map(|value| unescape_string(value)))
+        .collect();
+    // See https://tools.ietf.org/html/rfc5545#section-3.8.1.9
+    let priority = find_property_value(&ical_event.properties, "PRIORITY")
+        .and_then(|value| value.trim().parse::<u8>().ok())
+        .filter(|priority| *priority <= 9)
+        .unwrap_or(0);
+    // See https://tools.ietf.org/html/rfc5545#section-3.8.4.1
+    let num_participants = ical_event
+        .properties
+        .iter()
+        .filter(|p| p.name == "ATTENDEE")
+        .count();
+    let is_organizer = options.user_email.as_ref().map_or(false, |email| {
+        is_organized_by(&ical_event.properties, email)
+    });
+    // See https://tools.ietf.org/html/rfc5545#section-3.8.7.4
+    let sequence = find_property_value(&ical_event.properties, "SEQUENCE")
+        .and_then(|value| value.trim().parse::<u32>().ok())
+        .unwrap_or(0);
+    // See https://tools.ietf.org/html/rfc5545#section-3.8.7.3
+    let last_modified = find_property(&ical_event.properties, "LAST-MODIFIED").and_then(|prop| {
+        extract_ical_datetime(
+            prop,
+            calendar_timezones,
+            local_tz,
+            options.unknown_tz_policy,
+        )
+        .ok()
+    });
     Ok(Event {
+        uid,
         summary,
         description,
         location,
         meeturl,
+        meeting_provider,
+        dialin,
+        passcode,
+        comments,
+        attachments,
+        nof_inline_attachments,
+        busy,
+        tentative,
+        priority,
+        sequence,
+        last_modified,
+        num_participants,
+        is_organizer,
+        related_to,
+        alarm_lead_seconds: parse_alarm_leads(&ical_event.alarms),
+        calendar_name: calendar_name.map(str::to_string),
+        todo: false,
         all_day,
         start_timestamp,
         end_timestamp,
     })
 }
 
+/// Maps the to-dos of the calendar to all-day events on the day they are due. To-dos without a
+/// DUE date or with one we can not parse are skipped, they should not break the calendar.
+///
+/// See https://tools.ietf.org/html/rfc5545#section-3.6.2
+fn parse_todos(
+    todos: &[IcalTodo],
+    calendar_name: Option<&str>,
+    calendar_timezones: &HashMap<String, CustomTz>,
+    local_tz: &Tz,
+    options: &ExtractOptions,
+) -> Vec<Event> {
+    let preferred_language = options.preferred_language.as_deref();
+    todos
+        .iter()
+        .filter_map(|todo| {
+            let due_prop = find_property(&todo.properties, "DUE")?;
+            let due = if is_ical_date(due_prop) {
+                parse_ical_date_notz(require_value(due_prop).ok()?, local_tz).ok()?
+            } else {
+                extract_ical_datetime(
+                    due_prop,
+                    calendar_timezones,
+                    local_tz,
+                    options.unknown_tz_policy,
+                )
+                .ok()?
+            };
+            let due_day = local_tz
+                .ymd(due.year(), due.month(), due.day())
+                .and_hms(0, 0, 0);
+            Some(Event {
+                uid: find_property_value(&todo.properties, "UID").unwrap_or_else(|| "".to_string()),
+                summary: unescape_string(
+                    &find_localized_property_value(&todo.properties, "SUMMARY", preferred_language)
+                        .unwrap_or_else(|| "".to_string()),
+                ),
+                description: unescape_string(
+                    &find_localized_property_value(
+                        &todo.properties,
+                        "DESCRIPTION",
+                        preferred_language,
+                    )
+                    .unwrap_or_else(|| "".to_string()),
+                ),
+                location: "".to_string(),
+                meeturl: None,
+                meeting_provider: None,
+                dialin: None,
+                passcode: None,
+                comments: vec![],
+                attachments: vec![],
+                nof_inline_attachments: 0,
+                busy: false,
+                tentative: false,
+                priority: find_property_value(&todo.properties, "PRIORITY")
+                    .and_then(|value| value.trim().parse::<u8>().ok())
+                    .filter(|priority| *priority <= 9)
+                    .unwrap_or(0),
+                sequence: 0,
+                last_modified: None,
+                num_participants: 0,
+                is_organizer: false,
+                related_to: vec![],
+                alarm_lead_seconds: vec![],
+                calendar_name: calendar_name.map(str::to_string),
+                todo: true,
+                all_day: true,
+                start_timestamp: due_day,
+                end_timestamp: due_day + Duration::days(1),
+            })
+        })
+        .collect()
+}
+
 fn strip_param(p: &Property, param_name: &str) -> (Property, Option<String>) {
     let mut removed_param_value = None;
     let new_prop = Property {
@@ -240,6 +676,32 @@ fn strip_param(p: &Property, param_name: &str) -> (Property, Option<String>) {
     (new_prop, removed_param_value)
 }
 
+/// The maximum number of occurrences we calculate for rules that recur more often than hourly
+const MAX_SUB_HOURLY_OCCURRENCES: usize = 1000;
+
+/// Whether the RRULE has a frequency of less than an hour, i.e. SECONDLY or MINUTELY
+fn is_sub_hourly_rrule(rrule_prop: &Property) -> bool {
+    rrule_prop.value.as_ref().map_or(false, |value| {
+        value
+            .split(';')
+            .any(|component| component == "FREQ=SECONDLY" || component == "FREQ=MINUTELY")
+    })
+}
+
+/// Calculates the occurrences of a ruleset. Sub-hourly rules without COUNT or UNTIL (or with
+/// very large ones) would generate an enormous amount of occurrences, so for those we stop
+/// after a fixed number of occurrences.
+fn expand_ruleset(ruleset: RRuleSet, sub_hourly: bool) -> Vec<DateTime<Tz>> {
+    if sub_hourly {
+        ruleset
+            .into_iter()
+            .take(MAX_SUB_HOURLY_OCCURRENCES)
+            .collect()
+    } else {
+        ruleset.all()
+    }
+}
+
 /// This function will parse occurrences from an ical event by using the rrule library to expand
 /// the various rule definitions into concrete DateTimes representing recurring instances
 ///
@@ -265,11 +727,17 @@ fn parse_occurrences(
     properties: &[Property],
     custom_timezones: &HashMap<String, CustomTz>,
     local_tz: &Tz,
+    unknown_tz_policy: UnknownTzPolicy,
 ) -> Result<Vec<DateTime<Tz>>, CalendarError> {
     // if no DTSTART or RRULE is present we can't do anything and assume we can't calculate occurrences
     let maybe_dtstart_prop = find_property(properties, "DTSTART");
-    let maybe_rrule_prop = find_property(properties, "RRULE");
-    if maybe_dtstart_prop.is_none() || maybe_rrule_prop.is_none() {
+    // RFC 5545 discourages it, but some feeds attach more than one RRULE to an event. rrule
+    // supports multiple rules in a set so we just pass all of them and get the union of occurrences
+    let rrule_props_in_event: Vec<&Property> = properties
+        .iter()
+        .filter(|property| property.name == "RRULE")
+        .collect();
+    if maybe_dtstart_prop.is_none() || rrule_props_in_event.is_empty() {
         return Ok(vec![]);
     }
     // some preliminary data wrangling so the actual handling of all the cases is easier afterwards
@@ -281,18 +749,24 @@ fn parse_occurrences(
         .and_then(|params| find_param(params, "TZID"));
     let maybe_original_tz = if let Some(tzid_param) = maybe_tzid_param {
         let unescaped_tzid = unescape_string(&tzid_param[0]);
-        match parse_tzid(&unescaped_tzid, custom_timezones) {
-            Ok(original_tz) => Some(original_tz),
-            Err(e) => {
-                return Err(CalendarError {
-                    msg: format!("error in timezone string parsing: {}", e),
-                })
-            }
-        }
+        Some(resolve_tzid(
+            &unescaped_tzid,
+            custom_timezones,
+            local_tz,
+            unknown_tz_policy,
+        )?)
     } else {
         None
     };
-    let rrule_prop = maybe_rrule_prop.unwrap();
+    let sub_hourly = rrule_props_in_event
+        .iter()
+        .any(|rrule_prop| is_sub_hourly_rrule(rrule_prop));
+    if sub_hourly {
+        eprintln!(
+            "Event has a sub-hourly recurrence, only calculating the first {} occurrences",
+            MAX_SUB_HOURLY_OCCURRENCES
+        );
+    }
     let maybe_exdate_prop = find_property(properties, "EXDATE");
     let all_day_event = is_ical_date(dtstart_prop);
     // Prepare a vec of all relevant rrule properties for rrule to work on by stripping tzid parameters
@@ -312,11 +786,10 @@ fn parse_occurrences(
     //let take_occurrence_pred =
     // Case 1: DTSTART is a DATE
     if all_day_event {
-        rule_props.push(rrule_prop.clone());
+        rule_props.extend(rrule_props_in_event.into_iter().cloned());
         let event_as_string = properties_to_string(&rule_props);
         match event_as_string.parse::<RRuleSet>() {
-            Ok(ruleset) => Ok(ruleset
-                .all()
+            Ok(ruleset) => Ok(expand_ruleset(ruleset, sub_hourly)
                 .iter()
                 .skip_while(|d| skip_occurrence_pred(d))
                 .take_while(|d| take_occurrence_pred(d))
@@ -326,29 +799,28 @@ fn parse_occurrences(
                         .and_hms(0, 0, 0)
                 })
                 .collect()),
-            Err(e) => Err(CalendarError {
+            Err(e) => Err(CalendarError::Parse {
                 msg: format!("error in RRULE parsing: {}", e),
             }),
         }
     } else if maybe_tzid_param.is_none() && !dtstart_time_str.ends_with('Z') {
         // CASE 2: we have local datetimes with no timezone information, throw error?
-        Err(CalendarError {
+        Err(CalendarError::UnsupportedEvent {
             msg: "Found an event with a local timestamp without a timezone, this is unsupported"
                 .to_string(),
         })
     } else if maybe_tzid_param.is_none() && dtstart_time_str.ends_with('Z') {
         // CASE 3: UTC datetime, let rrule do its thing, we convert all occurrences to the local TZ
-        rule_props.push(rrule_prop.clone());
+        rule_props.extend(rrule_props_in_event.into_iter().cloned());
         let event_as_string = properties_to_string(&rule_props);
         match event_as_string.parse::<RRuleSet>() {
-            Ok(ruleset) => Ok(ruleset
-                .all()
+            Ok(ruleset) => Ok(expand_ruleset(ruleset, sub_hourly)
                 .iter()
                 .skip_while(|d| skip_occurrence_pred(d))
                 .take_while(|d| take_occurrence_pred(d))
                 .map(|dt| dt.with_timezone(local_tz))
                 .collect()),
-            Err(e) => Err(CalendarError {
+            Err(e) => Err(CalendarError::Parse {
                 msg: format!("error in RRULE parsing: {}", e),
             }),
         }
@@ -362,55 +834,56 @@ fn parse_occurrences(
         // Interpret all occurrences as original TZ, then convert to local TZ
         //
         // hard assumption that there is a value always in an rrule
-        let rrule_value = rrule_prop.value.as_ref().unwrap();
-        // RRULE is a bit special, the parameters are not actually in the params but they are encoded in the VALUE of the property
-        // we basically parse the value here and substitute the UNTIL component with a date that has a converted timestamp
-        let rrule_value_modified = rrule_value
-            .split(';')
-            .map(|rrule_component| {
-                if let Some(until_value) = rrule_component.strip_prefix("UNTIL=") {
-                    if until_value.ends_with('Z') {
-                        // NOTE we do not check whether maybe the parse failed, we hard assume it does
-                        let until_originaltz_str = if original_tz.is_left() {
-                            parse_ical_datetime(
-                                until_value.to_string().strip_suffix('Z').unwrap(),
-                                &Left(UTC),
-                                &original_tz.left().unwrap(),
-                            )
-                            .unwrap()
-                            .format("%Y%m%dT%H%M%S")
-                            .to_string()
+        for rrule_prop in &rrule_props_in_event {
+            let rrule_value = rrule_prop.value.as_ref().unwrap();
+            // RRULE is a bit special, the parameters are not actually in the params but they are encoded in the VALUE of the property
+            // we basically parse the value here and substitute the UNTIL component with a date that has a converted timestamp
+            let rrule_value_modified = rrule_value
+                .split(';')
+                .map(|rrule_component| {
+                    if let Some(until_value) = rrule_component.strip_prefix("UNTIL=") {
+                        if until_value.ends_with('Z') {
+                            // NOTE we do not check whether maybe the parse failed, we hard assume it does
+                            let until_originaltz_str = if original_tz.is_left() {
+                                parse_ical_datetime(
+                                    until_value.to_string().strip_suffix('Z').unwrap(),
+                                    &Left(UTC),
+                                    &original_tz.left().unwrap(),
+                                )
+                                .unwrap()
+                                .format("%Y%m%dT%H%M%S")
+                                .to_string()
+                            } else {
+                                parse_ical_datetime(
+                                    until_value.to_string().strip_suffix('Z').unwrap(),
+                                    &Left(UTC),
+                                    original_tz.right().unwrap(),
+                                )
+                                .unwrap()
+                                .format("%Y%m%dT%H%M%S")
+                                .to_string()
+                            };
+                            format!("UNTIL={}", until_originaltz_str)
                         } else {
-                            parse_ical_datetime(
-                                until_value.to_string().strip_suffix('Z').unwrap(),
-                                &Left(UTC),
-                                original_tz.right().unwrap(),
-                            )
-                            .unwrap()
-                            .format("%Y%m%dT%H%M%S")
-                            .to_string()
-                        };
-                        format!("UNTIL={}", until_originaltz_str)
+                            rrule_component.to_string()
+                        }
                     } else {
                         rrule_component.to_string()
                     }
-                } else {
-                    rrule_component.to_string()
-                }
-            })
-            .collect::<Vec<String>>()
-            .join(";");
-        let new_rule_prop = Property {
-            name: rrule_prop.name.clone(),
-            params: rrule_prop.params.clone(),
-            value: Some(rrule_value_modified),
-        };
-        rule_props.push(new_rule_prop);
+                })
+                .collect::<Vec<String>>()
+                .join(";");
+            let new_rule_prop = Property {
+                name: rrule_prop.name.clone(),
+                params: rrule_prop.params.clone(),
+                value: Some(rrule_value_modified),
+            };
+            rule_props.push(new_rule_prop);
+        }
         let event_as_string = properties_to_string(&rule_props);
         // println!("New RRULE string: {:?}", event_as_string);
         match event_as_string.parse::<RRuleSet>() {
-            Ok(ruleset) => Ok(ruleset
-                .all()
+            Ok(ruleset) => Ok(expand_ruleset(ruleset, sub_hourly)
                 .iter()
                 .skip_while(|d| skip_occurrence_pred(d))
                 .take_while(|d| take_occurrence_pred(d))
@@ -428,28 +901,20 @@ fn parse_occurrences(
                     //         .with_timezone(&local_tz)
                     // );
                     if original_tz.is_left() {
-                        original_tz
-                            .left()
-                            .unwrap()
-                            .from_local_datetime(original_datetime)
-                            .unwrap()
+                        resolve_local_datetime(&original_tz.left().unwrap(), original_datetime)
                             .with_timezone(local_tz)
                     } else {
-                        original_tz
-                            .right()
-                            .unwrap()
-                            .from_local_datetime(original_datetime)
-                            .unwrap()
+                        resolve_local_datetime(original_tz.right().unwrap(), original_datetime)
                             .with_timezone(local_tz)
                     }
                 })
                 .collect()),
-            Err(e) => Err(CalendarError {
+            Err(e) => Err(CalendarError::Parse {
                 msg: format!("error in RRULE parsing: {}", e),
             }),
         }
     } else {
-        Err(CalendarError {
+        Err(CalendarError::UnsupportedEvent {
             msg: "Unknown ical event date specification".to_string(),
         })
     }
@@ -462,6 +927,7 @@ fn partition_modifying_events(
     events: &[(IcalEvent, Event)],
     calendar_timezones: &HashMap<String, CustomTz>,
     local_tz: &Tz,
+    unknown_tz_policy: UnknownTzPolicy,
 ) -> (
     MultiMap<String, (IcalEvent, Event)>,
     Vec<(IcalEvent, Event)>,
@@ -475,7 +941,12 @@ fn partition_modifying_events(
         // presence of a RECURRENCE-ID property is the trigger to know this is a modifying event
         if let Some(recurrence_id_property) = find_property(&ical_event.properties, "RECURRENCE-ID")
         {
-            match extract_ical_datetime(recurrence_id_property, calendar_timezones, local_tz) {
+            match extract_ical_datetime(
+                recurrence_id_property,
+                calendar_timezones,
+                local_tz,
+                unknown_tz_policy,
+            ) {
                 Ok(_) => {
                     if let Some(uid) = find_property_value(&ical_event.properties, "UID") {
                         // println!("+MODIFYING EVENT: {:?}", ical_event);
@@ -486,9 +957,13 @@ fn partition_modifying_events(
             }
         } else {
             // println!("NON-MODIFYING EVENT: {:?}", ical_event);
-            let uid = find_property_value(&ical_event.properties, "UID").unwrap();
-            non_modifying_event_uids.insert(uid);
-            non_modifying_events.push((ical_event.clone(), event.clone()));
+            match find_property_value(&ical_event.properties, "UID") {
+                Some(uid) => {
+                    non_modifying_event_uids.insert(uid);
+                    non_modifying_events.push((ical_event.clone(), event.clone()));
+                }
+                None => eprintln!("Skipping event '{}' since it has no UID", event.summary),
+            }
         }
     }
     // We make sure that we only retain modifying events that actually modify a non-modifying event
@@ -506,12 +981,20 @@ fn partition_modifying_events(
     (modifying_events, non_modifying_events)
 }
 
+/// Some feeds mix CRLF, LF and even lone CR line endings. We normalize everything to LF so
+/// that no stray CR ends up in property values, where it would for example break the RRULE
+/// parsing and silently give us no occurrences.
+fn normalize_line_endings(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
 fn parse_calendar(text: &str) -> Result<Option<IcalCalendar>, CalendarError> {
-    let mut reader = ical::IcalParser::new(text.as_bytes());
+    let normalized_text = normalize_line_endings(text);
+    let mut reader = ical::IcalParser::new(normalized_text.as_bytes());
     match reader.next() {
         Some(result) => match result {
             Ok(calendar) => Ok(Some(calendar)),
-            Err(e) => Err(CalendarError {
+            Err(e) => Err(CalendarError::Parse {
                 msg: format!("error in ical parsing: {:?}", e),
             }),
         },
@@ -523,17 +1006,78 @@ fn parse_events(
     calendar: IcalCalendar,
     calendar_timezones: &HashMap<String, CustomTz>,
     local_tz: &Tz,
-) -> Result<Vec<(IcalEvent, Event)>, CalendarError> {
+    options: &ExtractOptions,
+) -> Vec<(IcalEvent, Event)> {
+    let calendar_name = parse_calendar_name(&calendar);
     calendar
         .events
         .into_iter()
-        .map(
-            |event| match parse_event(&event, calendar_timezones, local_tz) {
-                Ok(parsed_event) => Ok((event, parsed_event)),
-                Err(e) => Err(e),
-            },
-        )
-        .collect::<Result<Vec<(IcalEvent, Event)>, CalendarError>>() // will fail on the first parse error and return an error
+        .filter(|event| {
+            if options.unknown_tz_policy != UnknownTzPolicy::Error {
+                return true;
+            }
+            match find_unknown_tzid(event, calendar_timezones) {
+                Some(tzid) => {
+                    eprintln!(
+                        "Skipping event with UID '{}' since its timezone '{}' is unknown",
+                        find_property_value(&event.properties, "UID").unwrap_or_default(),
+                        tzid
+                    );
+                    false
+                }
+                None => true,
+            }
+        })
+        .filter_map(|event| {
+            match parse_event(
+                &event,
+                calendar_name.as_deref(),
+                calendar_timezones,
+                local_tz,
+                options,
+            ) {
+                Ok(parsed_event) => Some((event, parsed_event)),
+                Err(e) => {
+                    eprintln!(
+                        "Skipping event with UID '{}' since it can not be parsed: {}",
+                        find_property_value(&event.properties, "UID").unwrap_or_default(),
+                        e
+                    );
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Modifying events often only contain what changed, for example just the new time of a moved
+/// occurrence. Everything they leave out is inherited from the master event of the series.
+fn inherit_from_master(
+    modifying_ical_event: &IcalEvent,
+    modifying_event: &Event,
+    master_event: &Event,
+) -> Event {
+    let has_property = |name| find_property(&modifying_ical_event.properties, name).is_some();
+    let mut event = modifying_event.clone();
+    if !has_property("SUMMARY") {
+        event.summary = master_event.summary.clone();
+    }
+    if !has_property("LOCATION") {
+        event.location = master_event.location.clone();
+    }
+    if !has_property("DESCRIPTION") {
+        event.description = master_event.description.clone();
+        event.dialin = master_event.dialin.clone();
+        event.passcode = master_event.passcode.clone();
+    }
+    if modifying_ical_event.alarms.is_empty() {
+        event.alarm_lead_seconds = master_event.alarm_lead_seconds.clone();
+    }
+    if event.meeturl.is_none() {
+        event.meeturl = master_event.meeturl.clone();
+        event.meeting_provider = master_event.meeting_provider.clone();
+    }
+    event
 }
 
 fn calculate_occurrences(
@@ -543,14 +1087,22 @@ fn calculate_occurrences(
     modifying_events: &MultiMap<String, (IcalEvent, Event)>,
     calendar_timezones: &HashMap<String, CustomTz>,
     local_tz: &Tz,
+    unknown_tz_policy: UnknownTzPolicy,
 ) -> Vec<Event> {
+    let occurrence_uid = match find_property_value(&ical_event.properties, "UID") {
+        Some(uid) => uid,
+        None => {
+            eprintln!(
+                "Skipping event '{}' since it has no UID",
+                parsed_event.summary
+            );
+            return vec![];
+        }
+    };
     occurrences
         .iter()
-        .map(|datetime| {
+        .filter_map(|datetime| {
             // We need to figure out whether the occurrence can be used as such or whether it was changed by a modifying event
-            // We assume that each ical_event that is a recurring event has a UID, otherwise the unwrap will fail here.
-            // Needs more error handling?
-            let occurrence_uid = find_property_value(&ical_event.properties, "UID").unwrap();
             if modifying_events.contains_key(&occurrence_uid) {
                 let modifications = modifying_events.get_vec(&occurrence_uid).unwrap();
                 for (modifying_ical_event, modifying_event) in modifications {
@@ -561,49 +1113,296 @@ fn calculate_occurrences(
                     //     "Calculating start and end for recurrence event {}",
                     //     parsed_event.summary
                     // );
-                    let recurrence_datetime =
-                        extract_ical_datetime(recurrence_id_property, calendar_timezones, local_tz)
-                            .unwrap();
+                    let recurrence_datetime = extract_ical_datetime(
+                        recurrence_id_property,
+                        calendar_timezones,
+                        local_tz,
+                        unknown_tz_policy,
+                    )
+                    .unwrap();
                     if *datetime == recurrence_datetime {
+                        // a cancelled occurrence is simply left out
+                        if is_cancelled(&modifying_ical_event.properties) {
+                            return None;
+                        }
                         // the modifying event has the same UID as our event and it has the same timestamp, so we return the modification instead
-                        return modifying_event.clone();
+                        return Some(inherit_from_master(
+                            modifying_ical_event,
+                            modifying_event,
+                            parsed_event,
+                        ));
                     }
                 }
             }
             // we need to calculate this occurrence's end time by adding the duration of the original event to this particular start time
-            let end_time = *datetime
-                + Duration::seconds(
-                    parsed_event.end_timestamp.timestamp()
-                        - parsed_event.start_timestamp.timestamp(),
-                );
-            Event {
+            let end_time = if parsed_event.all_day {
+                // All day events span whole days, adding the duration in seconds would make them
+                // end an hour early or late when the span crosses a DST change
+                let nof_days = (parsed_event.end_timestamp.date().naive_local()
+                    - parsed_event.start_timestamp.date().naive_local())
+                .num_days();
+                let end_date = datetime.date().naive_local() + Duration::days(nof_days);
+                local_tz
+                    .ymd(end_date.year(), end_date.month(), end_date.day())
+                    .and_hms(0, 0, 0)
+            } else {
+                *datetime
+                    + Duration::seconds(
+                        parsed_event.end_timestamp.timestamp()
+                            - parsed_event.start_timestamp.timestamp(),
+                    )
+            };
+            Some(Event {
+                uid: parsed_event.uid.to_string(),
                 summary: parsed_event.summary.to_string(),
                 description: parsed_event.description.to_string(),
                 location: parsed_event.location.to_string(),
                 meeturl: parsed_event.meeturl.clone(),
+                meeting_provider: parsed_event.meeting_provider.clone(),
+                dialin: parsed_event.dialin.clone(),
+                passcode: parsed_event.passcode.clone(),
+                comments: parsed_event.comments.clone(),
+                attachments: parsed_event.attachments.clone(),
+                nof_inline_attachments: parsed_event.nof_inline_attachments,
+                busy: parsed_event.busy,
+                tentative: parsed_event.tentative,
+                priority: parsed_event.priority,
+                sequence: parsed_event.sequence,
+                last_modified: parsed_event.last_modified,
+                num_participants: parsed_event.num_participants,
+                is_organizer: parsed_event.is_organizer,
+                related_to: parsed_event.related_to.clone(),
+                alarm_lead_seconds: parsed_event.alarm_lead_seconds.clone(),
+                calendar_name: parsed_event.calendar_name.clone(),
+                todo: parsed_event.todo,
                 all_day: parsed_event.all_day,
                 start_timestamp: *datetime,
                 end_timestamp: end_time,
-            }
+            })
         })
         .collect()
 }
 
-pub fn extract_events(text: &str, local_tz: &Tz) -> Result<Vec<Event>, CalendarError> {
+/// Keeps at most `max_events` of the given events, preferring the ones closest to the current
+/// time since those are the events we actually display. The `distance_from_now` function
+/// determines how far away an event is in seconds.
+fn truncate_to_nearest_events<T, F>(
+    mut events: Vec<T>,
+    max_events: usize,
+    distance_from_now: F,
+) -> Vec<T>
+where
+    F: Fn(&T) -> i64,
+{
+    if events.len() > max_events {
+        eprintln!(
+            "Calendar contains {} events, this is more than the maximum of {}, only processing the {} events closest to now",
+            events.len(),
+            max_events,
+            max_events
+        );
+        events.sort_by_key(distance_from_now);
+        events.truncate(max_events);
+    }
+    events
+}
+
+/// Describes everything we know about the event with the given UID: its raw properties, how its
+/// timezone is resolved, its computed start and end and all its occurrences. This is meant to
+/// help with debugging events that show up at the wrong time.
+pub fn dump_event(
+    text: &str,
+    uid: &str,
+    local_tz: &Tz,
+    options: &ExtractOptions,
+) -> Result<String, CalendarError> {
+    let calendar = parse_calendar(text)?.ok_or_else(|| CalendarError::Parse {
+        msg: "The calendar contains no data".to_string(),
+    })?;
+    let calendar_timezones = parse_ical_timezones(&calendar, local_tz, options.debug_timezones)?;
+    let ical_events: Vec<&IcalEvent> = calendar
+        .events
+        .iter()
+        .filter(|ical_event| {
+            find_property_value(&ical_event.properties, "UID").as_deref() == Some(uid)
+        })
+        .collect();
+    if ical_events.is_empty() {
+        return Err(CalendarError::Parse {
+            msg: format!("There is no event with UID {}", uid),
+        });
+    }
+    let current_year = Local::now().year();
+    let mut lines = vec![
+        format!("Local timezone: {}", local_tz.name()),
+        format!(
+            "Recurrences are expanded for the years {} until {}",
+            current_year - 1,
+            current_year + 1
+        ),
+    ];
+    for ical_event in ical_events {
+        let kind = if find_property(&ical_event.properties, "RECURRENCE-ID").is_some() {
+            "Override of a single occurrence"
+        } else if find_property(&ical_event.properties, "RRULE").is_some() {
+            "Recurring event"
+        } else {
+            "Single event"
+        };
+        lines.push(format!("--- {} ---", kind));
+        lines.push(ical_event_to_string(ical_event));
+        let maybe_tzid = find_property(&ical_event.properties, "DTSTART")
+            .and_then(|dtstart| dtstart.params.as_ref())
+            .and_then(|params| find_param(params, "TZID"))
+            .map(|values| unescape_string(&values[0]));
+        if let Some(tzid) = maybe_tzid {
+            let resolved_tz = match parse_tzid(&tzid, &calendar_timezones) {
+                Ok(Left(tz)) => tz.name().to_string(),
+                Ok(Right(custom_tz)) => {
+                    format!("{} (from the calendar's VTIMEZONE)", custom_tz.name)
+                }
+                Err(e) => format!(
+                    "unknown ({}), {}",
+                    e,
+                    match options.unknown_tz_policy {
+                        UnknownTzPolicy::Local => "falling back to the local timezone",
+                        UnknownTzPolicy::Utc => "falling back to UTC",
+                        UnknownTzPolicy::Error => "the event is skipped",
+                    }
+                ),
+            };
+            lines.push(format!("Timezone {} is resolved as {}", tzid, resolved_tz));
+        }
+        match parse_event(
+            ical_event,
+            parse_calendar_name(&calendar).as_deref(),
+            &calendar_timezones,
+            local_tz,
+            options,
+        ) {
+            Ok(event) => {
+                lines.push(format!("Start: {}", event.start_timestamp.to_rfc3339()));
+                lines.push(format!("End: {}", event.end_timestamp.to_rfc3339()));
+                lines.push(format!("All day: {}", event.all_day));
+            }
+            Err(e) => lines.push(format!("Error parsing the event: {}", e.msg())),
+        }
+    }
+    let occurrences: Vec<Event> = extract_events(text, local_tz, options)?
+        .into_iter()
+        .filter(|event| event.uid == uid)
+        .collect();
+    lines.push(format!("--- {} occurrence(s) ---", occurrences.len()));
+    for occurrence in occurrences {
+        lines.push(format!(
+            "{} - {}: {}",
+            occurrence.start_timestamp.to_rfc3339(),
+            occurrence.end_timestamp.to_rfc3339(),
+            occurrence.summary
+        ));
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Returns all events that overlap with the range from start (inclusive) to end (exclusive),
+/// sorted by their start time. Events that span the boundaries of the range, like a meeting
+/// from 23:00 to 01:00 when asking for a single day, are returned for every range they
+/// overlap with. Events without a duration are returned when they start inside the range.
+pub fn events_in_range(events: &[Event], start: DateTime<Tz>, end: DateTime<Tz>) -> Vec<Event> {
+    let mut events_in_range = events
+        .iter()
+        .filter(|e| {
+            (e.start_timestamp < end && e.end_timestamp > start)
+                || (e.start_timestamp >= start && e.start_timestamp < end)
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    events_in_range.sort_by(|a, b| Ord::cmp(&a.start_timestamp, &b.start_timestamp));
+    events_in_range
+}
+
+pub fn extract_events(
+    text: &str,
+    local_tz: &Tz,
+    options: &ExtractOptions,
+) -> Result<Vec<Event>, CalendarError> {
+    extract_calendar(text, local_tz, options).map(|(events, _)| events)
+}
+
+/// How often the calendar asks to be polled, taken from its REFRESH-INTERVAL or the older
+/// X-PUBLISHED-TTL
+///
+/// See <https://tools.ietf.org/html/rfc7986#section-5.7>
+fn parse_refresh_interval(calendar: &IcalCalendar) -> Option<Duration> {
+    find_property_value(&calendar.properties, "REFRESH-INTERVAL")
+        .or_else(|| find_property_value(&calendar.properties, "X-PUBLISHED-TTL"))
+        .and_then(|value| parse_ical_duration(&value))
+}
+
+/// Extracts the events of the calendar together with its refresh interval, so that the calendar
+/// only needs to be parsed once
+fn extract_calendar(
+    text: &str,
+    local_tz: &Tz,
+    options: &ExtractOptions,
+) -> Result<(Vec<Event>, Option<Duration>), CalendarError> {
+    let now = Local::now().timestamp();
     match parse_calendar(text)? {
-        Some(calendar) => {
-            let calendar_timezones = parse_ical_timezones(&calendar, local_tz)?;
+        Some(mut calendar) => {
+            let refresh_interval = parse_refresh_interval(&calendar);
+            apply_cancel_method(&mut calendar);
+            let calendar_timezones =
+                parse_ical_timezones(&calendar, local_tz, options.debug_timezones)?;
             //println!("Calendar timezones found: {:?}", calendar_timezones);
-            let event_tuples = parse_events(calendar, &calendar_timezones, local_tz)?;
+            let todos = if options.include_todos {
+                parse_todos(
+                    &calendar.todos,
+                    parse_calendar_name(&calendar).as_deref(),
+                    &calendar_timezones,
+                    local_tz,
+                    options,
+                )
+            } else {
+                vec![]
+            };
+            let event_tuples = parse_events(calendar, &calendar_timezones, local_tz, options);
             // Events are either normal events (potentially recurring) or they are modifying events
             // that defines exceptions to recurrences of other events. We need to split these types out
-            let (modifying_events, non_modifying_events) =
-                partition_modifying_events(&event_tuples, &calendar_timezones, local_tz);
+            let (modifying_events, mut non_modifying_events) = partition_modifying_events(
+                &event_tuples,
+                &calendar_timezones,
+                local_tz,
+                options.unknown_tz_policy,
+            );
+            if let Some(max_events) = options.max_events {
+                // Recurring events can have occurrences close to now regardless of when the
+                // series started so we always prefer to keep them
+                non_modifying_events = truncate_to_nearest_events(
+                    non_modifying_events,
+                    max_events,
+                    |(ical_event, event)| {
+                        if find_property(&ical_event.properties, "RRULE").is_some() {
+                            0
+                        } else {
+                            (event.start_timestamp.timestamp() - now).abs()
+                        }
+                    },
+                );
+            }
             // Calculate occurrences for recurring events
             non_modifying_events
                 .into_iter()
                 .map(|(ical_event, parsed_event)| {
-                    match parse_occurrences(&ical_event.properties, &calendar_timezones, local_tz) {
+                    // a cancelled series vanishes completely, as does a cancelled single event
+                    if is_cancelled(&ical_event.properties) {
+                        return Ok(vec![]);
+                    }
+                    match parse_occurrences(
+                        &ical_event.properties,
+                        &calendar_timezones,
+                        local_tz,
+                        options.unknown_tz_policy,
+                    ) {
                         Ok(occurrences) => {
                             // println!("Occurrences for {:?}: {:?}", ical_event, occurrences);
                             if occurrences.is_empty() {
@@ -616,6 +1415,7 @@ pub fn extract_events(text: &str, local_tz: &Tz) -> Result<Vec<Event>, CalendarE
                                     &modifying_events,
                                     &calendar_timezones,
                                     local_tz,
+                                    options.unknown_tz_policy,
                                 ))
                             }
                         }
@@ -625,16 +1425,1063 @@ pub fn extract_events(text: &str, local_tz: &Tz) -> Result<Vec<Event>, CalendarE
                 // we now have replaced each event with a list of its occurrences
                 .collect::<Result<Vec<Vec<Event>>, CalendarError>>()
                 .map(|event_instances| {
-                    event_instances.into_iter().flatten().collect() // flatmap that shit
+                    let events: Vec<Event> = event_instances
+                        .into_iter()
+                        .flatten() // flatmap that shit
+                        .chain(todos)
+                        .filter(|event| {
+                            !options
+                                .hide_summary_patterns
+                                .iter()
+                                .any(|pattern| pattern.is_match(&event.summary))
+                        })
+                        .collect();
+                    let events = match options.max_events {
+                        Some(max_events) => {
+                            truncate_to_nearest_events(events, max_events, |event| {
+                                (event.start_timestamp.timestamp() - now).abs()
+                            })
+                        }
+                        None => events,
+                    };
+                    (events, refresh_interval)
                 })
         }
-        None => Ok(vec![]),
+        None => Ok((vec![], None)),
+    }
+}
+
+/// Remembers the events of the last extracted calendar so that an unchanged feed does not have
+/// all its recurrences expanded again on every poll. The occurrences we calculate and the events
+/// we keep depend on the current date, so the cache is also invalidated when the day changes.
+#[derive(Default)]
+pub struct ExtractionCache {
+    key: Option<(u64, NaiveDate)>,
+    events: Vec<Event>,
+    refresh_interval: Option<Duration>,
+}
+
+impl ExtractionCache {
+    pub fn extract_events(
+        &mut self,
+        text: &str,
+        local_tz: &Tz,
+        options: &ExtractOptions,
+    ) -> Result<Vec<Event>, CalendarError> {
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        let key = (hasher.finish(), Local::now().naive_local().date());
+        if self.key != Some(key) {
+            let (events, refresh_interval) = extract_calendar(text, local_tz, options)?;
+            self.events = events;
+            self.refresh_interval = refresh_interval;
+            self.key = Some(key);
+        }
+        Ok(self.events.clone())
+    }
+
+    /// How often the last extracted calendar asks to be polled, see `parse_refresh_interval`
+    pub fn refresh_interval(&self) -> Option<Duration> {
+        self.refresh_interval
+    }
+}
+
+/// Parses a positive duration like PT1H, P1D or P1W
+///
+/// See <https://tools.ietf.org/html/rfc5545#section-3.3.6>
+fn parse_ical_duration(value: &str) -> Option<Duration> {
+    lazy_static! {
+        static ref DURATION_REGEX: regex::Regex =
+            Regex::new(r"^\+?P(?:(\d+)W|(?:(\d+)D)?(?:T(?:(\d+)H)?(?:(\d+)M)?(?:(\d+)S)?)?)$")
+                .unwrap();
+    }
+    let captures = DURATION_REGEX.captures(value.trim())?;
+    let component = |index: usize, seconds: i64| {
+        captures
+            .get(index)
+            .and_then(|m| m.as_str().parse::<i64>().ok())
+            .map_or(0, |amount| amount * seconds)
+    };
+    let total_seconds = component(1, 7 * 24 * 3600)
+        + component(2, 24 * 3600)
+        + component(3, 3600)
+        + component(4, 60)
+        + component(5, 1);
+    if total_seconds > 0 {
+        Some(Duration::seconds(total_seconds))
+    } else {
+        None
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono_tz::Europe::Berlin;
+
+    fn calendar(events: &str) -> String {
+        format!(
+            "BEGIN:VCALENDAR\nVERSION:2.0\nPRODID:-//meeters//test//EN\n{}END:VCALENDAR\n",
+            events
+        )
+    }
+
+    /// A weekly meeting with three occurrences on the 5th, 12th and 19th of January. We use the
+    /// current year since occurrences are only calculated around it.
+    fn weekly_series(uid: &str, year: i32) -> String {
+        format!(
+            "BEGIN:VEVENT\nUID:{1}\nSUMMARY:Weekly\nDTSTART:{0}0105T090000Z\nDTEND:{0}0105T100000Z\nRRULE:FREQ=WEEKLY;COUNT=3\nEND:VEVENT\n",
+            year, uid
+        )
+    }
+
+    fn override_event(
+        uid: &str,
+        summary: &str,
+        recurrence_id: &str,
+        start: &str,
+        end: &str,
+    ) -> String {
+        format!(
+            "BEGIN:VEVENT\nUID:{}\nSUMMARY:{}\nRECURRENCE-ID:{}\nDTSTART:{}\nDTEND:{}\nEND:VEVENT\n",
+            uid, summary, recurrence_id, start, end
+        )
+    }
+
+    const VALID_EVENT: &str =
+        "BEGIN:VEVENT\nUID:valid\nSUMMARY:Valid\nDTSTART:20210105T090000Z\nDTEND:20210105T100000Z\nEND:VEVENT\n";
+
+    fn parse_test_events(text: &str) -> Vec<(IcalEvent, Event)> {
+        let calendar = parse_calendar(text).unwrap().unwrap();
+        parse_events(
+            calendar,
+            &HashMap::new(),
+            &Berlin,
+            &ExtractOptions::default(),
+        )
+    }
+
+    #[test]
+    fn modifying_event_with_base_series_is_kept_as_modifier() {
+        let year = Local::now().year();
+        let text = calendar(&format!(
+            "{}{}",
+            weekly_series("series", year),
+            override_event(
+                "series",
+                "Moved",
+                &format!("{}0112T090000Z", year),
+                &format!("{}0112T140000Z", year),
+                &format!("{}0112T150000Z", year)
+            )
+        ));
+        let (modifying_events, non_modifying_events) = partition_modifying_events(
+            &parse_test_events(&text),
+            &HashMap::new(),
+            &Berlin,
+            UnknownTzPolicy::Local,
+        );
+        assert_eq!(1, modifying_events.get_vec("series").unwrap().len());
+        assert_eq!(1, non_modifying_events.len());
+        assert_eq!("Weekly", non_modifying_events[0].1.summary);
+    }
+
+    #[test]
+    fn modifying_event_without_base_series_becomes_standalone_event() {
+        let year = Local::now().year();
+        let text = calendar(&format!(
+            "{}{}",
+            weekly_series("series", year),
+            override_event(
+                "forwarded",
+                "Forwarded",
+                &format!("{}0113T090000Z", year),
+                &format!("{}0113T090000Z", year),
+                &format!("{}0113T100000Z", year)
+            )
+        ));
+        let (modifying_events, non_modifying_events) = partition_modifying_events(
+            &parse_test_events(&text),
+            &HashMap::new(),
+            &Berlin,
+            UnknownTzPolicy::Local,
+        );
+        assert!(modifying_events.is_empty());
+        assert_eq!(2, non_modifying_events.len());
+        assert!(non_modifying_events
+            .iter()
+            .any(|(_, event)| event.summary == "Forwarded"));
+    }
+
+    #[test]
+    fn multiple_modifying_events_without_base_series_become_standalone_events_once() {
+        let year = Local::now().year();
+        let text = calendar(&format!(
+            "{}{}{}",
+            weekly_series("series", year),
+            override_event(
+                "forwarded",
+                "First",
+                &format!("{}0113T090000Z", year),
+                &format!("{}0113T090000Z", year),
+                &format!("{}0113T100000Z", year)
+            ),
+            override_event(
+                "forwarded",
+                "Second",
+                &format!("{}0120T090000Z", year),
+                &format!("{}0120T090000Z", year),
+                &format!("{}0120T100000Z", year)
+            )
+        ));
+        let (modifying_events, non_modifying_events) = partition_modifying_events(
+            &parse_test_events(&text),
+            &HashMap::new(),
+            &Berlin,
+            UnknownTzPolicy::Local,
+        );
+        assert!(modifying_events.is_empty());
+        let mut standalone_summaries: Vec<&str> = non_modifying_events
+            .iter()
+            .map(|(_, event)| event.summary.as_str())
+            .collect();
+        standalone_summaries.sort_unstable();
+        assert_eq!(vec!["First", "Second", "Weekly"], standalone_summaries);
+        let mut summaries: Vec<String> = extract_events(&text, &Berlin, &ExtractOptions::default())
+            .unwrap()
+            .into_iter()
+            .map(|event| event.summary)
+            .collect();
+        summaries.sort_unstable();
+        assert_eq!(
+            vec!["First", "Second", "Weekly", "Weekly", "Weekly"],
+            summaries
+        );
+    }
+
+    #[test]
+    fn time_only_override_inherits_from_master() {
+        let year = Local::now().year();
+        let text = calendar(&format!(
+            "BEGIN:VEVENT\nUID:series\nSUMMARY:Weekly\nLOCATION:https://zoom.us/j/123456\nDESCRIPTION:Passcode: 4711\nDTSTART:{0}0105T090000Z\nDTEND:{0}0105T100000Z\nRRULE:FREQ=WEEKLY;COUNT=3\nEND:VEVENT\nBEGIN:VEVENT\nUID:series\nRECURRENCE-ID:{0}0112T090000Z\nDTSTART:{0}0112T140000Z\nDTEND:{0}0112T150000Z\nEND:VEVENT\n",
+            year
+        ));
+        let mut events = extract_events(&text, &Berlin, &ExtractOptions::default()).unwrap();
+        events.sort_by(|a, b| Ord::cmp(&a.start_timestamp, &b.start_timestamp));
+        let moved = &events[1];
+        assert_eq!(
+            Berlin.ymd(year, 1, 12).and_hms(15, 0, 0),
+            moved.start_timestamp
+        );
+        assert_eq!("Weekly", moved.summary);
+        assert_eq!("https://zoom.us/j/123456", moved.location);
+        assert_eq!(Some("https://zoom.us/j/123456".to_string()), moved.meeturl);
+        assert_eq!(Some("4711".to_string()), moved.passcode);
+    }
+
+    #[test]
+    fn recurrence_id_overrides_matching_occurrence() {
+        let year = Local::now().year();
+        let text = calendar(&format!(
+            "{}{}",
+            weekly_series("series", year),
+            override_event(
+                "series",
+                "Moved",
+                &format!("{}0112T090000Z", year),
+                &format!("{}0112T140000Z", year),
+                &format!("{}0112T150000Z", year)
+            )
+        ));
+        let mut events = extract_events(&text, &Berlin, &ExtractOptions::default()).unwrap();
+        events.sort_by(|a, b| Ord::cmp(&a.start_timestamp, &b.start_timestamp));
+        assert_eq!(
+            vec!["Weekly", "Moved", "Weekly"],
+            events
+                .iter()
+                .map(|event| event.summary.as_str())
+                .collect::<Vec<&str>>()
+        );
+        assert_eq!(
+            Berlin.ymd(year, 1, 12).and_hms(15, 0, 0),
+            events[1].start_timestamp
+        );
+    }
+
+    #[test]
+    fn override_moving_occurrence_to_next_day_does_not_duplicate_it() {
+        let year = Local::now().year();
+        let text = calendar(&format!(
+            "{}{}",
+            weekly_series("series", year),
+            override_event(
+                "series",
+                "Weekly",
+                &format!("{}0112T090000Z", year),
+                &format!("{}0113T090000Z", year),
+                &format!("{}0113T100000Z", year)
+            )
+        ));
+        let mut events = extract_events(&text, &Berlin, &ExtractOptions::default()).unwrap();
+        events.sort_by(|a, b| Ord::cmp(&a.start_timestamp, &b.start_timestamp));
+        // the occurrence on the 12th is replaced by the one on the 13th and not added to it
+        assert_eq!(
+            vec![5, 13, 19],
+            events
+                .iter()
+                .map(|event| event.start_timestamp.day())
+                .collect::<Vec<u32>>()
+        );
+    }
+
+    #[test]
+    fn max_events_keeps_events_closest_to_now() {
+        let now = Utc::now();
+        let single_event = |uid: &str, days_from_now: i64| {
+            let start = now + Duration::days(days_from_now);
+            format!(
+                "BEGIN:VEVENT\nUID:{}\nSUMMARY:{}\nDTSTART:{}\nDTEND:{}\nEND:VEVENT\n",
+                uid,
+                uid,
+                start.format("%Y%m%dT%H%M%SZ"),
+                (start + Duration::hours(1)).format("%Y%m%dT%H%M%SZ")
+            )
+        };
+        let text = calendar(&format!(
+            "{}{}{}",
+            single_event("far-past", -30),
+            single_event("tomorrow", 1),
+            single_event("last-week", -7)
+        ));
+        let options = ExtractOptions {
+            max_events: Some(2),
+            ..ExtractOptions::default()
+        };
+        let mut summaries: Vec<String> = extract_events(&text, &Berlin, &options)
+            .unwrap()
+            .into_iter()
+            .map(|event| event.summary)
+            .collect();
+        summaries.sort();
+        assert_eq!(vec!["last-week", "tomorrow"], summaries);
+    }
+
+    #[test]
+    fn minutely_recurrence_is_bounded() {
+        let year = Local::now().year();
+        let text = calendar(&format!(
+            "BEGIN:VEVENT\nUID:minutely\nSUMMARY:Spam\nDTSTART:{0}0101T000000Z\nDTEND:{0}0101T000100Z\nRRULE:FREQ=MINUTELY\nEND:VEVENT\n",
+            year
+        ));
+        let ical_event = &parse_test_events(&text)[0].0;
+        let occurrences = parse_occurrences(
+            &ical_event.properties,
+            &HashMap::new(),
+            &Berlin,
+            UnknownTzPolicy::Local,
+        )
+        .unwrap();
+        assert_eq!(MAX_SUB_HOURLY_OCCURRENCES, occurrences.len());
+    }
+
+    #[test]
+    fn summary_in_preferred_language_is_used() {
+        let text = calendar(
+            "BEGIN:VEVENT\nUID:i18n\nSUMMARY:Meeting\nSUMMARY;LANGUAGE=de:Besprechung\nDESCRIPTION;LANGUAGE=en:Agenda\nDESCRIPTION;LANGUAGE=de:Tagesordnung\nDTSTART:20210105T090000Z\nDTEND:20210105T100000Z\nEND:VEVENT\n",
+        );
+        let options = ExtractOptions {
+            preferred_language: Some("de".to_string()),
+            ..ExtractOptions::default()
+        };
+        let events = extract_events(&text, &Berlin, &options).unwrap();
+        assert_eq!("Besprechung", events[0].summary);
+        assert_eq!("Tagesordnung", events[0].description);
+        let events = extract_events(&text, &Berlin, &ExtractOptions::default()).unwrap();
+        assert_eq!("Meeting", events[0].summary);
+        assert_eq!("Agenda", events[0].description);
+    }
+
+    #[test]
+    fn all_alarms_relative_to_the_start_are_used() {
+        let text = calendar(
+            "BEGIN:VEVENT\nUID:alarms\nSUMMARY:Review\nDTSTART:20220301T090000Z\nDTEND:20220301T100000Z\nBEGIN:VALARM\nACTION:DISPLAY\nTRIGGER:-PT10M\nEND:VALARM\nBEGIN:VALARM\nACTION:DISPLAY\nTRIGGER:-P1D\nEND:VALARM\nBEGIN:VALARM\nACTION:DISPLAY\nTRIGGER;RELATED=END:-PT5M\nEND:VALARM\nEND:VEVENT\n",
+        );
+        let events = extract_events(&text, &Berlin, &ExtractOptions::default()).unwrap();
+        assert_eq!(vec![24 * 3600, 600], events[0].alarm_lead_seconds);
+    }
+
+    #[test]
+    fn occurrences_are_tagged_with_the_calendar_name() {
+        let year = Local::now().year();
+        let text = calendar(&format!(
+            "X-WR-CALNAME:Team\\, Berlin\n{}",
+            weekly_series("series", year)
+        ));
+        let events = extract_events(&text, &Berlin, &ExtractOptions::default()).unwrap();
+        assert_eq!(3, events.len());
+        assert!(events
+            .iter()
+            .all(|event| event.calendar_name.as_deref() == Some("Team, Berlin")));
+        let events = extract_events(
+            &calendar(&weekly_series("series", year)),
+            &Berlin,
+            &ExtractOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(None, events[0].calendar_name);
+    }
+
+    #[test]
+    fn events_of_a_cancel_message_are_cancelled() {
+        let year = Local::now().year();
+        let events = |method: &str| {
+            let text = calendar(&format!(
+                "METHOD:{}\n{}{}",
+                method,
+                weekly_series("series", year),
+                "BEGIN:VEVENT\nUID:single\nSUMMARY:Single\nDTSTART:20220301T090000Z\nDTEND:20220301T100000Z\nEND:VEVENT\n"
+            ));
+            extract_events(&text, &Berlin, &ExtractOptions::default())
+                .unwrap()
+                .len()
+        };
+        assert_eq!(0, events("CANCEL"));
+        assert_eq!(4, events("PUBLISH"));
+    }
+
+    #[test]
+    fn calendar_without_version_and_prodid_is_parsed() {
+        let text = "BEGIN:VCALENDAR\nBEGIN:VEVENT\nUID:minimal\nSUMMARY:Minimal\nDTSTART:20220301T090000Z\nDTEND:20220301T100000Z\nEND:VEVENT\nEND:VCALENDAR\n";
+        let events = extract_events(text, &Berlin, &ExtractOptions::default()).unwrap();
+        assert_eq!(1, events.len());
+        assert_eq!("Minimal", events[0].summary);
+    }
+
+    #[test]
+    fn mixed_line_endings_still_give_occurrences() {
+        let year = Local::now().year();
+        let text = calendar(&weekly_series("crlf", year))
+            .replace('\n', "\r\n")
+            .replacen("\r\nEND:VEVENT", "\rEND:VEVENT", 1);
+        let events = extract_events(&text, &Berlin, &ExtractOptions::default()).unwrap();
+        assert_eq!(3, events.len());
+    }
+
+    #[test]
+    fn recurring_multi_day_all_day_events_keep_their_span() {
+        let year = Local::now().year();
+        // start on the first saturday of march so that one of the weekends spans the DST change
+        let mut first_saturday = NaiveDate::from_ymd(year, 3, 1);
+        while first_saturday.weekday() != Weekday::Sat {
+            first_saturday = first_saturday.succ();
+        }
+        let first_monday = first_saturday + Duration::days(2);
+        let text = calendar(&format!(
+            "BEGIN:VEVENT\nUID:oncall\nSUMMARY:On Call\nDTSTART;VALUE=DATE:{}\nDTEND;VALUE=DATE:{}\nRRULE:FREQ=WEEKLY;BYDAY=SA;COUNT=5\nEND:VEVENT\n",
+            first_saturday.format("%Y%m%d"),
+            first_monday.format("%Y%m%d")
+        ));
+        let events = extract_events(&text, &Berlin, &ExtractOptions::default()).unwrap();
+        assert_eq!(5, events.len());
+        for event in events {
+            assert!(event.all_day);
+            assert_eq!(Weekday::Sat, event.start_timestamp.weekday());
+            assert_eq!(0, event.start_timestamp.hour());
+            assert_eq!(Weekday::Mon, event.end_timestamp.weekday());
+            assert_eq!(0, event.end_timestamp.hour());
+        }
+    }
+
+    #[test]
+    fn events_matching_hide_patterns_are_dropped() {
+        let year = Local::now().year();
+        let text = calendar(&format!(
+            "{}{}{}",
+            weekly_series("series", year),
+            override_event(
+                "series",
+                "Focus time",
+                &format!("{}0112T090000Z", year),
+                &format!("{}0112T090000Z", year),
+                &format!("{}0112T100000Z", year)
+            ),
+            "BEGIN:VEVENT\nUID:lunch\nSUMMARY:Lunch break\nDTSTART:20220105T120000Z\nDTEND:20220105T130000Z\nEND:VEVENT\n"
+        ));
+        let options = ExtractOptions {
+            hide_summary_patterns: vec![
+                Regex::new("^Focus time$").unwrap(),
+                Regex::new("(?i)lunch").unwrap(),
+            ],
+            ..ExtractOptions::default()
+        };
+        let summaries: Vec<String> = extract_events(&text, &Berlin, &options)
+            .unwrap()
+            .into_iter()
+            .map(|event| event.summary)
+            .collect();
+        assert_eq!(vec!["Weekly", "Weekly"], summaries);
+    }
+
+    fn extract_with_unknown_tz_policy(unknown_tz_policy: UnknownTzPolicy) -> Vec<(String, u32)> {
+        let year = Local::now().year();
+        let text = calendar(&format!(
+            "BEGIN:VEVENT\nUID:single\nSUMMARY:Single\nDTSTART;TZID=Mars Standard Time:{0}0105T090000\nDTEND;TZID=Mars Standard Time:{0}0105T100000\nEND:VEVENT\n\
+             BEGIN:VEVENT\nUID:series\nSUMMARY:Series\nDTSTART;TZID=Mars Standard Time:{0}0106T090000\nDTEND;TZID=Mars Standard Time:{0}0106T100000\nRRULE:FREQ=WEEKLY;COUNT=2\nEND:VEVENT\n\
+             BEGIN:VEVENT\nUID:known\nSUMMARY:Known\nDTSTART;TZID=Europe/Berlin:{0}0107T090000\nDTEND;TZID=Europe/Berlin:{0}0107T100000\nEND:VEVENT\n",
+            year
+        ));
+        let options = ExtractOptions {
+            unknown_tz_policy,
+            ..ExtractOptions::default()
+        };
+        let mut events = extract_events(&text, &Berlin, &options).unwrap();
+        events.sort_by_key(|event| event.start_timestamp);
+        events
+            .into_iter()
+            .map(|event| (event.summary, event.start_timestamp.hour()))
+            .collect()
+    }
+
+    #[test]
+    fn unknown_timezones_are_local_by_default() {
+        assert_eq!(
+            vec![
+                ("Single".to_string(), 9),
+                ("Series".to_string(), 9),
+                ("Known".to_string(), 9),
+                ("Series".to_string(), 9)
+            ],
+            extract_with_unknown_tz_policy(UnknownTzPolicy::default())
+        );
+    }
+
+    #[test]
+    fn unknown_timezones_can_be_treated_as_utc() {
+        assert_eq!(
+            vec![
+                ("Single".to_string(), 10),
+                ("Series".to_string(), 10),
+                ("Known".to_string(), 9),
+                ("Series".to_string(), 10)
+            ],
+            extract_with_unknown_tz_policy(UnknownTzPolicy::Utc)
+        );
+    }
+
+    #[test]
+    fn events_with_unknown_timezones_can_be_skipped() {
+        assert_eq!(
+            vec![("Known".to_string(), 9)],
+            extract_with_unknown_tz_policy(UnknownTzPolicy::Error)
+        );
+    }
+
+    #[test]
+    fn extraction_cache_is_invalidated_when_the_feed_changes() {
+        let year = Local::now().year();
+        let series = calendar(&weekly_series("series", year));
+        let single = calendar(
+            "BEGIN:VEVENT\nUID:single\nSUMMARY:Single\nDTSTART:20220105T120000Z\nDTEND:20220105T130000Z\nEND:VEVENT\n",
+        );
+        let options = ExtractOptions::default();
+        let mut cache = ExtractionCache::default();
+        assert_eq!(
+            3,
+            cache
+                .extract_events(&series, &Berlin, &options)
+                .unwrap()
+                .len()
+        );
+        let series_key = cache.key;
+        assert_eq!(
+            3,
+            cache
+                .extract_events(&series, &Berlin, &options)
+                .unwrap()
+                .len()
+        );
+        assert_eq!(series_key, cache.key);
+        assert_eq!(
+            1,
+            cache
+                .extract_events(&single, &Berlin, &options)
+                .unwrap()
+                .len()
+        );
+        assert_ne!(series_key, cache.key);
+        // a feed that fails to parse does not replace the cached events
+        assert!(cache
+            .extract_events(
+                "BEGIN:VCALENDAR\nBEGIN:VEVENT\nSUMMARY:Broken\n",
+                &Berlin,
+                &options
+            )
+            .is_err());
+        assert_eq!(1, cache.events.len());
+    }
+
+    #[test]
+    fn long_descriptions_are_truncated_after_extraction() {
+        let quoted_thread = "> Lorem ipsum dolor sit amet\\n".repeat(1000);
+        let text = calendar(&format!(
+            "BEGIN:VEVENT\nUID:long\nSUMMARY:Long\nDTSTART:20220105T120000Z\nDTEND:20220105T130000Z\nDESCRIPTION:{}Join at https://acme.zoom.us/j/123456789\\nPasscode: 424242\nEND:VEVENT\n",
+            quoted_thread
+        ));
+        let options = ExtractOptions {
+            max_description_len: Some(100),
+            ..ExtractOptions::default()
+        };
+        let event = &extract_events(&text, &Berlin, &options).unwrap()[0];
+        assert_eq!(
+            Some("https://acme.zoom.us/j/123456789".to_string()),
+            event.meeturl
+        );
+        assert_eq!(Some("424242".to_string()), event.passcode);
+        assert_eq!(100, event.description.chars().count());
+        assert!(event.description.ends_with('…'));
+    }
+
+    #[test]
+    fn ical_durations_are_parsed() {
+        assert_eq!(Some(Duration::hours(1)), parse_ical_duration("PT1H"));
+        assert_eq!(Some(Duration::days(1)), parse_ical_duration("P1D"));
+        assert_eq!(Some(Duration::weeks(2)), parse_ical_duration("P2W"));
+        assert_eq!(
+            Some(Duration::seconds(24 * 3600 + 2 * 3600 + 30 * 60 + 5)),
+            parse_ical_duration("P1DT2H30M5S")
+        );
+        assert_eq!(None, parse_ical_duration("PT0S"));
+        assert_eq!(None, parse_ical_duration("-PT1H"));
+        assert_eq!(None, parse_ical_duration("P"));
+        assert_eq!(None, parse_ical_duration("1 hour"));
+    }
+
+    #[test]
+    fn refresh_interval_is_taken_from_the_calendar() {
+        let text = "BEGIN:VCALENDAR\nVERSION:2.0\nPRODID:-//meeters//test//EN\nX-PUBLISHED-TTL:PT12H\nREFRESH-INTERVAL;VALUE=DURATION:PT1H\nEND:VCALENDAR\n";
+        let mut cache = ExtractionCache::default();
+        cache
+            .extract_events(text, &Berlin, &ExtractOptions::default())
+            .unwrap();
+        assert_eq!(Some(Duration::hours(1)), cache.refresh_interval());
+        cache
+            .extract_events(&calendar(""), &Berlin, &ExtractOptions::default())
+            .unwrap();
+        assert_eq!(None, cache.refresh_interval());
+    }
+
+    #[test]
+    fn cancelled_series_has_no_occurrences() {
+        let year = Local::now().year();
+        let text = calendar(&format!(
+            "{}{}",
+            weekly_series("series", year).replace("RRULE", "STATUS:CANCELLED\nRRULE"),
+            "BEGIN:VEVENT\nUID:single\nSUMMARY:Single\nDTSTART:20220105T120000Z\nDTEND:20220105T130000Z\nEND:VEVENT\n"
+        ));
+        let summaries: Vec<String> = extract_events(&text, &Berlin, &ExtractOptions::default())
+            .unwrap()
+            .into_iter()
+            .map(|event| event.summary)
+            .collect();
+        assert_eq!(vec!["Single"], summaries);
+    }
+
+    #[test]
+    fn cancelled_occurrence_is_left_out_of_the_series() {
+        let year = Local::now().year();
+        let text = calendar(&format!(
+            "{}{}",
+            weekly_series("series", year),
+            override_event(
+                "series",
+                "Weekly",
+                &format!("{}0112T090000Z", year),
+                &format!("{}0112T090000Z", year),
+                &format!("{}0112T100000Z", year)
+            )
+            .replace("END:VEVENT", "STATUS:CANCELLED\nEND:VEVENT")
+        ));
+        let mut starts: Vec<u32> = extract_events(&text, &Berlin, &ExtractOptions::default())
+            .unwrap()
+            .into_iter()
+            .map(|event| event.start_timestamp.day())
+            .collect();
+        starts.sort_unstable();
+        assert_eq!(vec![5, 19], starts);
+    }
+
+    #[test]
+    fn todos_with_due_date_become_all_day_events() {
+        let text = calendar(
+            "BEGIN:VTODO\nUID:report\nSUMMARY:Send report\nDUE;VALUE=DATE:20220310\nEND:VTODO\nBEGIN:VTODO\nUID:taxes\nSUMMARY:Taxes\nDUE:20220315T220000Z\nEND:VTODO\nBEGIN:VTODO\nUID:someday\nSUMMARY:Someday\nEND:VTODO\n",
+        );
+        assert!(extract_events(&text, &Berlin, &ExtractOptions::default())
+            .unwrap()
+            .is_empty());
+        let options = ExtractOptions {
+            include_todos: true,
+            ..ExtractOptions::default()
+        };
+        let mut todos = extract_events(&text, &Berlin, &options).unwrap();
+        todos.sort_by_key(|todo| todo.start_timestamp);
+        assert_eq!(2, todos.len());
+        assert!(todos
+            .iter()
+            .all(|todo| todo.todo && todo.all_day && !todo.busy));
+        assert_eq!(
+            Berlin.ymd(2022, 3, 10).and_hms(0, 0, 0),
+            todos[0].start_timestamp
+        );
+        assert_eq!(
+            Berlin.ymd(2022, 3, 11).and_hms(0, 0, 0),
+            todos[0].end_timestamp
+        );
+        // 22:00 UTC is already the next day in Berlin
+        assert_eq!(
+            Berlin.ymd(2022, 3, 16).and_hms(0, 0, 0),
+            todos[1].start_timestamp
+        );
+    }
+
+    fn dtstart(value: &str) -> Property {
+        Property {
+            name: "DTSTART".to_string(),
+            params: None,
+            value: Some(value.to_string()),
+        }
+    }
+
+    #[test]
+    fn datetime_with_fractional_seconds_and_zulu_time() {
+        assert_eq!(
+            Berlin.ymd(2022, 3, 1).and_hms(10, 30, 0),
+            extract_ical_datetime(
+                &dtstart("20220301T093000.000Z"),
+                &HashMap::new(),
+                &Berlin,
+                UnknownTzPolicy::Local
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            Berlin.ymd(2022, 3, 1).and_hms(9, 30, 15),
+            extract_ical_datetime(
+                &dtstart("20220301T093015.5"),
+                &HashMap::new(),
+                &Berlin,
+                UnknownTzPolicy::Local
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn datetime_with_embedded_offset() {
+        assert_eq!(
+            Berlin.ymd(2022, 3, 1).and_hms(8, 30, 0),
+            extract_ical_datetime(
+                &dtstart("20220301T093000+0200"),
+                &HashMap::new(),
+                &Berlin,
+                UnknownTzPolicy::Local
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn unsupported_datetime_is_an_error() {
+        let error = extract_ical_datetime(
+            &dtstart("2022-03-01 09:30"),
+            &HashMap::new(),
+            &Berlin,
+            UnknownTzPolicy::Local,
+        )
+        .unwrap_err();
+        assert!(error.msg().contains("2022-03-01 09:30"));
+        assert!(matches!(error, CalendarError::Parse { .. }));
+    }
+
+    #[test]
+    fn unknown_timezone_is_a_timezone_error() {
+        let mut prop = dtstart("20220301T093000");
+        prop.params = Some(vec![(
+            "TZID".to_string(),
+            vec!["Mars Standard Time".to_string()],
+        )]);
+        let error = extract_ical_datetime(&prop, &HashMap::new(), &Berlin, UnknownTzPolicy::Error)
+            .unwrap_err();
+        assert!(matches!(error, CalendarError::Timezone { .. }));
+    }
+
+    #[test]
+    fn recurring_events_with_custom_timezone_keep_local_time_across_dst() {
+        let year = Local::now().year();
+        // the series starts on the first of march and runs for six weeks, so it always crosses
+        // the switch to daylight saving time on the last sunday of march
+        let text = calendar(&format!(
+            "BEGIN:VTIMEZONE\nTZID:W. Europe Standard Time\nBEGIN:STANDARD\nDTSTART:16010101T030000\nTZOFFSETFROM:+0200\nTZOFFSETTO:+0100\nRRULE:FREQ=YEARLY;INTERVAL=1;BYDAY=-1SU;BYMONTH=10\nEND:STANDARD\nBEGIN:DAYLIGHT\nDTSTART:16010101T020000\nTZOFFSETFROM:+0100\nTZOFFSETTO:+0200\nRRULE:FREQ=YEARLY;INTERVAL=1;BYDAY=-1SU;BYMONTH=3\nEND:DAYLIGHT\nEND:VTIMEZONE\nBEGIN:VEVENT\nUID:weekly-berlin\nSUMMARY:Weekly\nDTSTART;TZID=W. Europe Standard Time:{0}0301T093000\nDTEND;TZID=W. Europe Standard Time:{0}0301T100000\nRRULE:FREQ=WEEKLY;COUNT=6\nEND:VEVENT\n",
+            year
+        ));
+        // we look at the meeting from UTC to make sure the conversion to the local timezone of
+        // the viewer is correct on both sides of the transition
+        let mut events =
+            extract_events(&text, &chrono_tz::UTC, &ExtractOptions::default()).unwrap();
+        events.sort_by_key(|event| event.start_timestamp);
+        assert_eq!(6, events.len());
+        for event in &events {
+            let start = event.start_timestamp.with_timezone(&Berlin);
+            let end = event.end_timestamp.with_timezone(&Berlin);
+            assert_eq!((9, 30), (start.hour(), start.minute()));
+            assert_eq!((10, 0), (end.hour(), end.minute()));
+        }
+        assert_eq!(8, events.first().unwrap().start_timestamp.hour());
+        assert_eq!(7, events.last().unwrap().start_timestamp.hour());
+    }
+
+    #[test]
+    fn dump_event_describes_series_and_overrides() {
+        let year = Local::now().year();
+        let text = calendar(&format!(
+            "{}{}",
+            weekly_series("dumped", year),
+            override_event(
+                "dumped",
+                "Moved",
+                &format!("{}0112T090000Z", year),
+                &format!("{}0112T140000Z", year),
+                &format!("{}0112T150000Z", year)
+            )
+        ));
+        let dump = dump_event(&text, "dumped", &Berlin, &ExtractOptions::default()).unwrap();
+        assert!(dump.contains("--- Recurring event ---"));
+        assert!(dump.contains("--- Override of a single occurrence ---"));
+        assert!(dump.contains("RRULE:FREQ=WEEKLY;COUNT=3"));
+        assert!(dump.contains("--- 3 occurrence(s) ---"));
+        assert!(dump_event(&text, "unknown", &Berlin, &ExtractOptions::default()).is_err());
+    }
+
+    #[test]
+    fn multiple_rrules_are_unioned() {
+        let year = Local::now().year();
+        let text = calendar(&format!(
+            "BEGIN:VEVENT\nUID:tworules\nSUMMARY:Two Rules\nDTSTART;TZID=Europe/Berlin:{0}0105T090000\nDTEND;TZID=Europe/Berlin:{0}0105T100000\nRRULE:FREQ=WEEKLY;COUNT=2\nRRULE:FREQ=MONTHLY;BYMONTHDAY=20;UNTIL={0}0121T000000Z\nEND:VEVENT\n",
+            year
+        ));
+        let ical_event = &parse_test_events(&text)[0].0;
+        let mut occurrences = parse_occurrences(
+            &ical_event.properties,
+            &HashMap::new(),
+            &Berlin,
+            UnknownTzPolicy::Local,
+        )
+        .unwrap();
+        occurrences.sort();
+        assert_eq!(
+            vec![5, 12, 20],
+            occurrences.iter().map(|o| o.day()).collect::<Vec<u32>>()
+        );
+        assert!(occurrences.iter().all(|o| o.hour() == 9));
+    }
+
+    #[test]
+    fn event_with_empty_dtstart_is_skipped() {
+        let events = parse_test_events(&calendar(&format!(
+            "BEGIN:VEVENT\nUID:broken\nSUMMARY:Broken\nDTSTART:\nDTEND:20210105T100000Z\nEND:VEVENT\n{}",
+            VALID_EVENT
+        )));
+        assert_eq!(1, events.len());
+        assert_eq!("Valid", events[0].1.summary);
+    }
+
+    #[test]
+    fn event_without_dtstart_is_skipped() {
+        let events = parse_test_events(&calendar(&format!(
+            "BEGIN:VEVENT\nUID:broken\nSUMMARY:Broken\nDTEND:20210105T100000Z\nEND:VEVENT\n{}",
+            VALID_EVENT
+        )));
+        assert_eq!(1, events.len());
+        assert_eq!("Valid", events[0].1.summary);
+    }
+
+    #[test]
+    fn parses_dialin_with_pin() {
+        assert_eq!(
+            Some("+1-555-123-4567 PIN 12345".to_string()),
+            parse_dialin("Dial +1-555-123-4567, PIN 12345")
+        );
+    }
+
+    #[test]
+    fn parses_dialin_with_conference_id() {
+        assert_eq!(
+            Some("+49 30 12345678 PIN 123456789".to_string()),
+            parse_dialin("Or join by phone\n+49 30 12345678\nConference ID: 123 456 789#")
+        );
+    }
+
+    #[test]
+    fn parses_dialin_without_pin() {
+        assert_eq!(
+            Some("+1 (555) 123-4567".to_string()),
+            parse_dialin("Call-in number: +1 (555) 123-4567")
+        );
+    }
+
+    #[test]
+    fn parses_passcodes() {
+        assert_eq!(
+            Some("987654".to_string()),
+            parse_passcode("Meeting ID: 123 456 7890\nPasscode: 987654")
+        );
+        assert_eq!(
+            Some("Xy7kQ2".to_string()),
+            parse_passcode("Meeting ID: 312 402 531 27\nPasscode: Xy7kQ2\nDownload Teams")
+        );
+        assert_eq!(
+            Some("4711".to_string()),
+            parse_passcode("Password 4711, see you there")
+        );
+        assert_eq!(Some("a1b2".to_string()), parse_passcode("Kenncode: a1b2"));
+        assert_eq!(None, parse_passcode("This meeting is password protected"));
+    }
+
+    #[test]
+    fn ignores_numbers_without_dialin_keyword() {
+        assert_eq!(None, parse_dialin("Room 1234567 on floor 3"));
+    }
+
+    #[test]
+    fn parses_all_comments() {
+        let text = calendar(
+            "BEGIN:VEVENT\nUID:comments\nSUMMARY:Workshop\nDTSTART:20210105T090000Z\nDTEND:20210105T100000Z\nCOMMENT:Bring laptop\nCOMMENT:Lunch is provided\\, vegetarian options available\nEND:VEVENT\n",
+        );
+        let event = &parse_test_events(&text)[0].1;
+        assert_eq!(
+            vec![
+                "Bring laptop".to_string(),
+                "Lunch is provided, vegetarian options available".to_string()
+            ],
+            event.comments
+        );
+    }
+
+    #[test]
+    fn parses_uri_attachments_and_counts_inline_ones() {
+        let text = calendar(
+            "BEGIN:VEVENT\nUID:attachments\nSUMMARY:Review\nDTSTART:20210105T090000Z\nDTEND:20210105T100000Z\nATTACH:https://example.com/agenda.pdf\nATTACH;FMTTYPE=text/plain;ENCODING=BASE64;VALUE=BINARY:SGVsbG8=\nATTACH;FMTTYPE=application/pdf:https://example.com/dialin.pdf\nEND:VEVENT\n",
+        );
+        let event = &parse_test_events(&text)[0].1;
+        assert_eq!(
+            vec![
+                "https://example.com/agenda.pdf".to_string(),
+                "https://example.com/dialin.pdf".to_string()
+            ],
+            event.attachments
+        );
+        assert_eq!(1, event.nof_inline_attachments);
+    }
+
+    #[test]
+    fn parses_sequence_and_last_modified() {
+        let text = calendar(
+            "BEGIN:VEVENT\nUID:revised\nSUMMARY:Revised\nDTSTART:20210105T090000Z\nDTEND:20210105T100000Z\nSEQUENCE:3\nLAST-MODIFIED:20210101T120000Z\nEND:VEVENT\nBEGIN:VEVENT\nUID:original\nSUMMARY:Original\nDTSTART:20210105T090000Z\nDTEND:20210105T100000Z\nEND:VEVENT\n",
+        );
+        let events = parse_test_events(&text);
+        assert_eq!(3, events[0].1.sequence);
+        assert_eq!(
+            Some(Berlin.ymd(2021, 1, 1).and_hms(13, 0, 0)),
+            events[0].1.last_modified
+        );
+        assert_eq!(0, events[1].1.sequence);
+        assert_eq!(None, events[1].1.last_modified);
+    }
+
+    #[test]
+    fn counts_attendees_as_participants() {
+        let text = calendar(
+            "BEGIN:VEVENT\nUID:team\nSUMMARY:Team\nDTSTART:20210105T090000Z\nDTEND:20210105T100000Z\nORGANIZER:mailto:boss@example.com\nATTENDEE;CN=Boss:mailto:boss@example.com\nATTENDEE;CN=Me:mailto:me@example.com\nATTENDEE;CN=You:mailto:you@example.com\nEND:VEVENT\n",
+        );
+        assert_eq!(3, parse_test_events(&text)[0].1.num_participants);
+    }
+
+    #[test]
+    fn organizer_is_compared_to_user_email() {
+        let text = calendar(
+            "BEGIN:VEVENT\nUID:mine\nSUMMARY:Mine\nDTSTART:20210105T090000Z\nDTEND:20210105T100000Z\nORGANIZER;CN=Me:MAILTO:Me@Example.com\nEND:VEVENT\nBEGIN:VEVENT\nUID:theirs\nSUMMARY:Theirs\nDTSTART:20210105T090000Z\nDTEND:20210105T100000Z\nORGANIZER;CN=Boss:mailto:boss@example.com\nEND:VEVENT\n",
+        );
+        let options = ExtractOptions {
+            user_email: Some("me@example.com".to_string()),
+            ..ExtractOptions::default()
+        };
+        let events = extract_events(&text, &Berlin, &options).unwrap();
+        assert!(events[0].is_organizer);
+        assert!(!events[1].is_organizer);
+        let events = extract_events(&text, &Berlin, &ExtractOptions::default()).unwrap();
+        assert!(!events[0].is_organizer);
+    }
+
+    #[test]
+    fn parses_tentative_status_and_priority() {
+        let text = calendar(
+            "BEGIN:VEVENT\nUID:maybe\nSUMMARY:Maybe\nDTSTART:20210105T090000Z\nDTEND:20210105T100000Z\nSTATUS:TENTATIVE\nPRIORITY:1\nEND:VEVENT\nBEGIN:VEVENT\nUID:sure\nSUMMARY:Sure\nDTSTART:20210105T090000Z\nDTEND:20210105T100000Z\nSTATUS:CONFIRMED\nPRIORITY:42\nEND:VEVENT\n",
+        );
+        let events = parse_test_events(&text);
+        assert!(events[0].1.tentative);
+        assert_eq!(1, events[0].1.priority);
+        assert!(!events[1].1.tentative);
+        assert_eq!(0, events[1].1.priority);
+    }
+
+    #[test]
+    fn parses_related_events() {
+        let text = calendar(
+            "BEGIN:VEVENT\nUID:followup\nSUMMARY:Follow-up\nDTSTART:20210105T090000Z\nDTEND:20210105T100000Z\nRELATED-TO:kickoff@example.com\nRELATED-TO;RELTYPE=SIBLING:review@example.com\nEND:VEVENT\n",
+        );
+        assert_eq!(
+            vec![
+                "kickoff@example.com".to_string(),
+                "review@example.com".to_string()
+            ],
+            parse_test_events(&text)[0].1.related_to
+        );
+    }
+
+    #[test]
+    fn microsoft_free_busy_status_is_not_busy() {
+        let text = calendar(
+            "BEGIN:VEVENT\nUID:free\nSUMMARY:Focus Time\nDTSTART:20210105T090000Z\nDTEND:20210105T100000Z\nX-MICROSOFT-CDO-BUSYSTATUS:FREE\nEND:VEVENT\nBEGIN:VEVENT\nUID:oof\nSUMMARY:Vacation\nDTSTART:20210105T090000Z\nDTEND:20210105T100000Z\nX-MICROSOFT-CDO-BUSYSTATUS:OOF\nEND:VEVENT\nBEGIN:VEVENT\nUID:opaque\nSUMMARY:Meeting\nDTSTART:20210105T090000Z\nDTEND:20210105T100000Z\nTRANSP:OPAQUE\nX-MICROSOFT-CDO-BUSYSTATUS:FREE\nEND:VEVENT\n",
+        );
+        let events = parse_test_events(&text);
+        assert!(!events[0].1.busy);
+        assert!(events[1].1.busy);
+        assert!(events[2].1.busy);
+    }
+
+    #[test]
+    fn quoted_tzid_is_stripped_and_the_rrule_still_parses() {
+        let tzid = "(UTC+01:00) Amsterdam, Berlin, Bern, Rome, Stockholm, Vienna";
+        let text = calendar(&format!(
+            "BEGIN:VEVENT\nUID:quoted\nSUMMARY:Quoted\nDTSTART;TZID=\"{0}\":20220301T090000\nDTEND;TZID=\"{0}\":20220301T100000\nRRULE:FREQ=WEEKLY;COUNT=3\nEXDATE;TZID=\"{0}\":20220308T090000\nEND:VEVENT\n",
+            tzid
+        ));
+        let calendar = parse_calendar(&text).unwrap().unwrap();
+        let properties = &calendar.events[0].properties;
+        let (dtstart, dtstart_tzid) =
+            strip_param(find_property(properties, "DTSTART").unwrap(), "TZID");
+        let (exdate, _) = strip_param(find_property(properties, "EXDATE").unwrap(), "TZID");
+        assert_eq!(Some(tzid.to_string()), dtstart_tzid);
+        let rule_string = properties_to_string(&[
+            dtstart,
+            exdate,
+            find_property(properties, "RRULE").unwrap().clone(),
+        ]);
+        assert_eq!(
+            "DTSTART:20220301T090000\nEXDATE:20220308T090000\nRRULE:FREQ=WEEKLY;COUNT=3",
+            rule_string
+        );
+        assert_eq!(2, rule_string.parse::<RRuleSet>().unwrap().all().len());
+    }
 
     // Fixed: https://github.com/fmeringdal/rust_rrule/issues/2
     #[test]
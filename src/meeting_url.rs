@@ -0,0 +1,118 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// Finds the URL of an online meeting of a particular provider in a text
+pub trait MeetingUrlResolver: Send + Sync {
+    /// Returns the meeting URL and the name of the provider when the text contains one
+    fn resolve(&self, text: &str) -> Option<(String, String)>;
+}
+
+/// Most providers can be recognized by the form of their meeting URLs alone
+struct RegexResolver {
+    provider: &'static str,
+    regex: Regex,
+}
+
+impl RegexResolver {
+    fn new(provider: &'static str, pattern: &str) -> RegexResolver {
+        RegexResolver {
+            provider,
+            regex: Regex::new(pattern).unwrap(),
+        }
+    }
+}
+
+impl MeetingUrlResolver for RegexResolver {
+    fn resolve(&self, text: &str) -> Option<(String, String)> {
+        self.regex
+            .find(text)
+            .map(|mat| (mat.as_str().to_string(), self.provider.to_string()))
+    }
+}
+
+lazy_static! {
+    /// All resolvers in the order of their priority, the first one that finds a URL wins
+    static ref MEETING_URL_RESOLVERS: Vec<Box<dyn MeetingUrlResolver>> = vec![
+        Box::new(RegexResolver::new(
+            "Zoom",
+            r"https?://[^\s]*zoom.us/(j|my)/[^\s\n\r<>]+"
+        )),
+        Box::new(RegexResolver::new(
+            "Teams",
+            r"https://teams\.microsoft\.com/l/meetup-join/[^\s<>]+"
+        )),
+        Box::new(RegexResolver::new(
+            "Meet",
+            r"https://meet\.google\.com/[a-z]{3}-[a-z]{4}-[a-z]{3}"
+        )),
+        Box::new(RegexResolver::new(
+            "Webex",
+            r"https://[\w-]+\.webex\.com/(?:meet/|join/|[\w-]+/j\.php)[^\s<>]*"
+        )),
+        Box::new(RegexResolver::new("Jitsi", r"https://meet\.jit\.si/[^\s<>]+")),
+    ];
+}
+
+/// Asks every resolver in order of priority for a meeting URL in the given texts. The texts are
+/// also in order of priority, usually the location, summary and description of an event.
+pub fn resolve_meeting_url(texts: &[&str]) -> Option<(String, String)> {
+    MEETING_URL_RESOLVERS
+        .iter()
+        .find_map(|resolver| texts.iter().find_map(|text| resolver.resolve(text)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(text: &str) -> Option<(String, String)> {
+        resolve_meeting_url(&[text])
+    }
+
+    #[test]
+    fn builtin_providers_are_recognized() {
+        assert_eq!(
+            Some((
+                "https://acme.zoom.us/j/123456789?pwd=abc".to_string(),
+                "Zoom".to_string()
+            )),
+            resolve("Join at https://acme.zoom.us/j/123456789?pwd=abc please")
+        );
+        assert_eq!(
+            Some((
+                "https://meet.google.com/abc-defg-hij".to_string(),
+                "Meet".to_string()
+            )),
+            resolve("https://meet.google.com/abc-defg-hij")
+        );
+        assert_eq!(
+            Some((
+                "https://acme.webex.com/meet/jdoe".to_string(),
+                "Webex".to_string()
+            )),
+            resolve("<https://acme.webex.com/meet/jdoe>")
+        );
+        assert_eq!(
+            Some((
+                "https://meet.jit.si/Standup".to_string(),
+                "Jitsi".to_string()
+            )),
+            resolve("https://meet.jit.si/Standup")
+        );
+        assert_eq!(
+            Some("Teams".to_string()),
+            resolve("https://teams.microsoft.com/l/meetup-join/19%3ameeting_abc%40thread.v2/0")
+                .map(|(_, provider)| provider)
+        );
+        assert_eq!(None, resolve("https://example.com/j/123"));
+    }
+
+    #[test]
+    fn resolvers_take_precedence_over_text_order() {
+        assert_eq!(
+            Some("Zoom".to_string()),
+            resolve_meeting_url(&["https://meet.jit.si/Standup", "https://zoom.us/j/1"])
+                .map(|(_, provider)| provider)
+        );
+    }
+}
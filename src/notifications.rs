@@ -0,0 +1,148 @@
+use notify_rust::{Notification, Urgency};
+use std::process::Command;
+use std::sync::mpsc;
+use std::thread;
+
+/// Everything needed to show a notification independent of the way it is delivered
+pub struct NotificationContent {
+    pub summary: String,
+    pub body: String,
+    /// A standard freedesktop.org icon name, see https://specifications.freedesktop.org/icon-naming-spec/icon-naming-spec-latest.html
+    pub icon: String,
+    pub urgency: Urgency,
+    /// Pairs of action identifier and the label of its button
+    pub actions: Vec<(String, String)>,
+}
+
+/// Delivers notifications to the desktop
+pub trait NotificationBackend {
+    /// Shows the notification and, when the backend supports actions, calls the handler with
+    /// the identifier of the action the user picks. This returns as soon as the notification is
+    /// shown, the handler may be called later from another thread.
+    fn show(
+        &self,
+        content: &NotificationContent,
+        on_action: Box<dyn FnMut(&str) + Send>,
+    ) -> Result<(), String>;
+}
+
+/// Talks to the notification daemon directly over D-Bus, the only backend with action buttons
+pub struct DbusBackend;
+
+impl NotificationBackend for DbusBackend {
+    fn show(
+        &self,
+        content: &NotificationContent,
+        mut on_action: Box<dyn FnMut(&str) + Send>,
+    ) -> Result<(), String> {
+        let mut notification = Notification::new();
+        notification
+            .summary(&content.summary)
+            .body(&content.body)
+            .icon(&content.icon)
+            .urgency(content.urgency);
+        for (identifier, label) in &content.actions {
+            notification.action(identifier, label);
+        }
+        let wait_for_action = !content.actions.is_empty();
+        // Waiting for an action blocks until the user reacts, we are usually called from the GTK
+        // main loop so that has to happen on its own thread. We only wait here until the
+        // notification is shown so that errors can still be reported to the caller.
+        let (shown_sender, shown_receiver) = mpsc::channel();
+        thread::spawn(move || match notification.show() {
+            Ok(handle) => {
+                let _ = shown_sender.send(Ok(()));
+                if wait_for_action {
+                    handle.wait_for_action(|action| on_action(action));
+                }
+            }
+            Err(e) => {
+                let _ = shown_sender.send(Err(e.to_string()));
+            }
+        });
+        shown_receiver
+            .recv()
+            .unwrap_or_else(|e| Err(format!("Notification thread failed: {}", e)))
+    }
+}
+
+/// Shells out to `notify-send` for environments where the D-Bus connection of notify-rust does
+/// not work, for example WSLg. Actions are not supported and are silently dropped.
+pub struct NotifySendBackend;
+
+fn notify_send_args(content: &NotificationContent) -> Vec<String> {
+    let urgency = match content.urgency {
+        Urgency::Low => "low",
+        Urgency::Normal => "normal",
+        Urgency::Critical => "critical",
+    };
+    vec![
+        format!("--urgency={}", urgency),
+        format!("--icon={}", content.icon),
+        "--app-name=meeters".to_string(),
+        "--".to_string(),
+        content.summary.clone(),
+        content.body.clone(),
+    ]
+}
+
+impl NotificationBackend for NotifySendBackend {
+    fn show(
+        &self,
+        content: &NotificationContent,
+        _on_action: Box<dyn FnMut(&str) + Send>,
+    ) -> Result<(), String> {
+        let status = Command::new("notify-send")
+            .args(notify_send_args(content))
+            .status()
+            .map_err(|e| format!("Can not run notify-send: {}", e))?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("notify-send failed with {}", status))
+        }
+    }
+}
+
+/// Uses D-Bus and falls back to `notify-send` when that does not work
+pub struct AutoBackend;
+
+impl NotificationBackend for AutoBackend {
+    fn show(
+        &self,
+        content: &NotificationContent,
+        on_action: Box<dyn FnMut(&str) + Send>,
+    ) -> Result<(), String> {
+        // notify-send has no actions, so there is nothing to hand the handler to
+        DbusBackend
+            .show(content, on_action)
+            .or_else(|_| NotifySendBackend.show(content, Box::new(|_| {})))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notify_send_gets_urgency_icon_and_text() {
+        let content = NotificationContent {
+            summary: "09:00 - Standup".to_string(),
+            body: "No Online Meeting".to_string(),
+            icon: "appointment-new".to_string(),
+            urgency: Urgency::Critical,
+            actions: vec![("dismiss".to_string(), "Dismiss".to_string())],
+        };
+        assert_eq!(
+            vec![
+                "--urgency=critical",
+                "--icon=appointment-new",
+                "--app-name=meeters",
+                "--",
+                "09:00 - Standup",
+                "No Online Meeting"
+            ],
+            notify_send_args(&content)
+        );
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+/// The meetings the user pinned for quick access. We store the UIDs of the events, for a
+/// recurring meeting this pins the entire series.
+pub struct PinnedMeetings {
+    path: PathBuf,
+    uids: HashSet<String>,
+}
+
+impl PinnedMeetings {
+    /// Loads the pinned meetings from the given file which contains one UID per line. A missing
+    /// or unreadable file just means that nothing was pinned yet.
+    pub fn load(path: PathBuf) -> PinnedMeetings {
+        let uids = match fs::read_to_string(&path) {
+            Ok(contents) => contents
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(|line| line.to_string())
+                .collect(),
+            Err(_) => HashSet::new(),
+        };
+        PinnedMeetings { path, uids }
+    }
+
+    pub fn is_pinned(&self, uid: &str) -> bool {
+        self.uids.contains(uid)
+    }
+
+    pub fn uids(&self) -> &HashSet<String> {
+        &self.uids
+    }
+
+    pub fn set_pinned(&mut self, uid: &str, pinned: bool) {
+        if pinned {
+            self.uids.insert(uid.to_string());
+        } else {
+            self.uids.remove(uid);
+        }
+        self.save();
+    }
+
+    fn save(&self) {
+        let contents = self
+            .uids
+            .iter()
+            .map(|uid| format!("{}\n", uid))
+            .collect::<String>();
+        if let Err(e) = fs::write(&self.path, contents) {
+            eprintln!(
+                "Could not save pinned meetings to {}: {}",
+                self.path.to_str().unwrap(),
+                e
+            );
+        }
+    }
+}
@@ -0,0 +1,27 @@
+/// Shortens the text to at most max_len characters, replacing the end with an ellipsis when
+/// it is too long. We count characters and not bytes so we never split a UTF-8 sequence.
+pub fn truncate_with_ellipsis(text: &str, max_len: usize) -> String {
+    if text.chars().count() <= max_len {
+        text.to_string()
+    } else if max_len == 0 {
+        "".to_string()
+    } else {
+        let mut truncated: String = text.chars().take(max_len - 1).collect();
+        truncated.push('…');
+        truncated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_labels_are_truncated_by_characters() {
+        assert_eq!("Standup", truncate_with_ellipsis("Standup", 7));
+        assert_eq!("Stand…", truncate_with_ellipsis("Standup", 6));
+        assert_eq!("Größ…", truncate_with_ellipsis("Größenwahn", 5));
+        assert_eq!("🎉🎉…", truncate_with_ellipsis("🎉🎉🎉🎉", 3));
+        assert_eq!("", truncate_with_ellipsis("Standup", 0));
+    }
+}
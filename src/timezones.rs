@@ -8,6 +8,7 @@ use crate::ical_util::unescape_string;
 use crate::CalendarError;
 use chrono::prelude::*;
 use chrono::DateTime;
+use chrono::LocalResult;
 use chrono_tz::Tz;
 use either::Either;
 use either::Left;
@@ -21,6 +22,30 @@ use std::collections::HashMap;
 
 use crate::windows_timezones::*;
 
+/// Converts a local datetime in the given timezone to an actual datetime. Local times around a
+/// DST transition need special care, we follow RFC 5545 here:
+/// * a local time that occurs twice because the clocks are turned back refers to the first
+///   occurrence, so it uses the offset from before the transition
+/// * a local time that does not exist because the clocks are turned forward is interpreted
+///   using the offset from before the gap
+///
+/// See <https://tools.ietf.org/html/rfc5545#section-3.3.5>
+pub fn resolve_local_datetime<T: TimeZone>(tz: &T, local: &NaiveDateTime) -> DateTime<T> {
+    match tz.from_local_datetime(local) {
+        LocalResult::Single(datetime) => datetime,
+        LocalResult::Ambiguous(first, _second) => first,
+        LocalResult::None => {
+            // a day earlier we are safely before the gap
+            let offset_before_gap = tz
+                .offset_from_utc_datetime(&(*local - chrono::Duration::days(1)))
+                .fix();
+            tz.from_utc_datetime(
+                &(*local - chrono::Duration::seconds(offset_before_gap.local_minus_utc() as i64)),
+            )
+        }
+    }
+}
+
 fn parse_windows_tzid(tzid: &str) -> Result<Tz, String> {
     match WINDOWS_TZ_TO_CHRONO_TZ.get(tzid) {
         Some(tz) => Ok(*tz),
@@ -75,16 +100,25 @@ fn parse_standard_tz(tzid: &str) -> Result<Tz, String> {
     }
 }
 
-/// Parses the VTIMEZONEs from the calendar and returns a map from timezone id to CustomTz
+/// Parses the VTIMEZONEs from the calendar and returns a map from timezone id to CustomTz. In
+/// debug mode the transitions of each timezone are logged.
 pub fn parse_ical_timezones(
     calendar: &IcalCalendar,
     local_tz: &Tz,
+    debug: bool,
 ) -> Result<HashMap<String, CustomTz>, CalendarError> {
-    calendar
+    let timezones = calendar
         .timezones
         .iter()
         .map(|vtimezone| parse_ical_timezone(vtimezone, local_tz))
-        .collect()
+        .collect::<Result<HashMap<String, CustomTz>, CalendarError>>()?;
+    if debug {
+        let utc_now = Utc::now().naive_utc();
+        for timezone in timezones.values() {
+            println!("{}", timezone.describe(&utc_now));
+        }
+    }
+    Ok(timezones)
 }
 
 fn parse_ical_timezone(
@@ -106,7 +140,7 @@ fn parse_ical_timezone(
             );
             Ok((unescaped_name, timezone))
         }
-        None => Err(CalendarError {
+        None => Err(CalendarError::Timezone {
             msg: "Expecting TZID property for custom timezone".to_string(),
         }),
     }
@@ -126,7 +160,7 @@ fn parse_occurrences_from_timespan(
     let maybe_dtstart_prop = find_property(properties, "DTSTART");
     let maybe_rrule_prop = find_property(properties, "RRULE");
     if maybe_dtstart_prop.is_none() {
-        return Err(CalendarError {
+        return Err(CalendarError::Timezone {
             msg: "Invalid definition for timespan, missing DTSTART".to_string(),
         });
     }
@@ -167,7 +201,7 @@ fn parse_occurrences_from_timespan(
                     Ok(relevant_transitions)
                 }
             }
-            Err(e) => Err(CalendarError {
+            Err(e) => Err(CalendarError::Timezone {
                 msg: format!("error in RRULE parsing: {}", e),
             }),
         }
@@ -181,8 +215,8 @@ fn parse_occurrences_from_timespan(
         // See also https://icalendar.org/iCalendar-RFC-5545/3-6-5-time-zone-component.html
         let date_time_str = maybe_dtstart_prop.unwrap().value.as_ref().unwrap();
         match NaiveDateTime::parse_from_str(date_time_str, "%Y%m%dT%H%M%S") {
-            Ok(dt) => return Ok(vec![local_tz.from_local_datetime(&dt).unwrap()]),
-            Err(e) => Err(CalendarError {
+            Ok(dt) => return Ok(vec![resolve_local_datetime(local_tz, &dt)]),
+            Err(e) => Err(CalendarError::Timezone {
                 msg: format!(
                     "Could not parse DTSTART for timezone timespan with value {:?} and error: {:?}",
                     date_time_str, e
@@ -229,7 +263,7 @@ fn parse_timespansets(
                 }
             }
             Err(e) => {
-                return Err(CalendarError {
+                return Err(CalendarError::Timezone {
                     msg: format!("error in RRULE parsing for timezone transition: {}, this is for ical timezone {:?}", e, vtimezone),
                 })
             }
@@ -279,25 +313,55 @@ fn parse_icaltimezonetransition(
     Ok(TimezoneTransition {
         properties: transition.properties.to_owned(),
         _offsetfrom: offset_to_seconds(
-            find_property_value(&transition.properties, "TZOFFSETFROM").ok_or(CalendarError {
-                msg: "no TZOFFSETFROM in timezone transition".to_string(),
-            })?,
-        ),
+            &find_property_value(&transition.properties, "TZOFFSETFROM").ok_or(
+                CalendarError::Timezone {
+                    msg: "no TZOFFSETFROM in timezone transition".to_string(),
+                },
+            )?,
+        )?,
         offsetto: offset_to_seconds(
-            find_property_value(&transition.properties, "TZOFFSETTO").ok_or(CalendarError {
-                msg: "no TZOFFSETTO in timezone transition".to_string(),
-            })?,
-        ),
+            &find_property_value(&transition.properties, "TZOFFSETTO").ok_or(
+                CalendarError::Timezone {
+                    msg: "no TZOFFSETTO in timezone transition".to_string(),
+                },
+            )?,
+        )?,
     })
 }
 
 /// Converts offsets in string form like "+0200" or more generally
-/// "+HHMM" to the matching number of seconds.
-fn offset_to_seconds(offset: String) -> i32 {
-    let mut seconds = 0;
-    seconds += offset[..3].parse::<i32>().unwrap() * 3600;
-    seconds += offset[3..].parse::<i32>().unwrap() * 60;
-    seconds
+/// "+HHMM" to the matching number of seconds. RFC 5545 also allows
+/// seconds ("+HHMMSS") and we accept just the hours ("+HH") as well.
+/// The sign applies to the offset as a whole, so "-0430" is minus
+/// four and a half hours.
+///
+/// See <https://tools.ietf.org/html/rfc5545#section-3.3.14>
+fn offset_to_seconds(offset: &str) -> Result<i32, CalendarError> {
+    let invalid_offset = || CalendarError::Timezone {
+        msg: format!("Invalid UTC offset '{}'", offset),
+    };
+    let sign = match offset.chars().next() {
+        Some('+') => 1,
+        Some('-') => -1,
+        _ => return Err(invalid_offset()),
+    };
+    let digits = &offset[1..];
+    if !(digits.len() == 2 || digits.len() == 4 || digits.len() == 6)
+        || !digits.chars().all(|c| c.is_ascii_digit())
+    {
+        return Err(invalid_offset());
+    }
+    // we verified that we only have pairs of ascii digits so the parse can not fail
+    let component = |start: usize| {
+        digits
+            .get(start..start + 2)
+            .map_or(0, |c| c.parse::<i32>().unwrap())
+    };
+    let (hours, minutes, seconds) = (component(0), component(2), component(4));
+    if minutes >= 60 || seconds >= 60 {
+        return Err(invalid_offset());
+    }
+    Ok(sign * (hours * 3600 + minutes * 60 + seconds))
 }
 
 // Example custom timezone by Exchange for Western European Standard Time
@@ -358,6 +422,117 @@ mod tests {
     use super::*;
     use chrono_tz::Europe::{Berlin, Dublin, Vienna};
 
+    /// A custom timezone that switches from CEST to CET on 2021-10-31 at 01:00 UTC, so the local
+    /// times between 02:00 and 03:00 occur twice
+    fn fall_back_tz() -> CustomTz {
+        CustomTz {
+            name: "fallback".to_string(),
+            timespanset: FixedTimespanSet {
+                first: FixedTimespan {
+                    utc_offset: 3600,
+                    dst_offset: 3600,
+                    name: "CEST",
+                },
+                rest: vec![(
+                    Utc.ymd(2021, 10, 31).and_hms(1, 0, 0).timestamp(),
+                    FixedTimespan {
+                        utc_offset: 3600,
+                        dst_offset: 0,
+                        name: "CET",
+                    },
+                )],
+            },
+        }
+    }
+
+    #[test]
+    fn ambiguous_custom_tz_local_time_uses_first_occurrence() {
+        let tz = fall_back_tz();
+        let local = NaiveDate::from_ymd(2021, 10, 31).and_hms(2, 30, 0);
+        assert!(matches!(
+            tz.from_local_datetime(&local),
+            LocalResult::Ambiguous(_, _)
+        ));
+        let resolved = resolve_local_datetime(&tz, &local);
+        assert_eq!(7200, resolved.offset().fix().local_minus_utc());
+        assert_eq!(
+            Utc.ymd(2021, 10, 31).and_hms(0, 30, 0),
+            resolved.with_timezone(&Utc)
+        );
+    }
+
+    #[test]
+    fn nonexistent_local_time_uses_offset_before_gap() {
+        // Berlin skips from 02:00 to 03:00 on 2021-03-28
+        let local = NaiveDate::from_ymd(2021, 3, 28).and_hms(2, 30, 0);
+        let resolved = resolve_local_datetime(&Berlin, &local);
+        assert_eq!(
+            Utc.ymd(2021, 3, 28).and_hms(1, 30, 0),
+            resolved.with_timezone(&Utc)
+        );
+    }
+
+    #[test]
+    fn parses_offsets_with_hours_minutes_and_seconds() {
+        assert_eq!(7200, offset_to_seconds("+02").unwrap());
+        assert_eq!(7200, offset_to_seconds("+0200").unwrap());
+        assert_eq!(-18000, offset_to_seconds("-0500").unwrap());
+        assert_eq!(3723, offset_to_seconds("+010203").unwrap());
+        assert_eq!(-3723, offset_to_seconds("-010203").unwrap());
+    }
+
+    #[test]
+    fn minutes_of_negative_offsets_have_the_sign_of_the_hours() {
+        // half hour offsets like those of India or Newfoundland
+        assert_eq!(-16200, offset_to_seconds("-0430").unwrap());
+        assert_eq!(19800, offset_to_seconds("+0530").unwrap());
+        assert_eq!(-12600, offset_to_seconds("-0330").unwrap());
+        assert_eq!(0, offset_to_seconds("-0000").unwrap());
+    }
+
+    #[test]
+    fn rejects_malformed_offsets() {
+        for offset in [
+            "",
+            "+",
+            "0200",
+            "+2",
+            "+020",
+            "+02000",
+            "+0200000",
+            "+02:00",
+            "+0260",
+            "+020060",
+            "+ab00",
+            "+02\u{e9}",
+        ] {
+            assert!(
+                offset_to_seconds(offset).is_err(),
+                "expected '{}' to be rejected",
+                offset
+            );
+        }
+    }
+
+    #[test]
+    fn offsets_round_trip_for_all_valid_values() {
+        for hours in 0..24 {
+            for minutes in 0..60 {
+                for seconds in [0, 30, 59] {
+                    let expected = hours * 3600 + minutes * 60 + seconds;
+                    for (sign, factor) in [("+", 1), ("-", -1)] {
+                        let hhmm = format!("{}{:02}{:02}", sign, hours, minutes);
+                        let hhmmss = format!("{}{:02}", hhmm, seconds);
+                        assert_eq!(factor * expected, offset_to_seconds(&hhmmss).unwrap());
+                        if seconds == 0 {
+                            assert_eq!(factor * expected, offset_to_seconds(&hhmm).unwrap());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
     #[test]
     fn parses_iana_strings() {
         assert_eq!(Berlin, parse_standard_tz("Europe/Berlin").unwrap());